@@ -0,0 +1,62 @@
+//! Throughput of sending a static file over a local socket, zero-copy vs buffered
+//!
+//! run with `cargo bench --bench sendfile`
+
+use std::fs::File;
+use std::io::{self, Read};
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use http_server::server::util::sendfile::{copy_buffered, send_file};
+
+const FILE_SIZE: usize = 64 * 1024 * 1024;
+const ITERATIONS: u32 = 10;
+
+/// Time sending the file once over a fresh local connection whose other end just drains it
+fn time_transfer(file: &mut File, transfer: fn(&mut TcpStream, &mut File, u64) -> io::Result<()>) -> Duration {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let drain = thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut buffer = vec![0; 256 * 1024];
+        let mut received = 0;
+        while let Ok(size) = stream.read(&mut buffer) {
+            if size == 0 {
+                break;
+            }
+            received += size;
+        }
+        received
+    });
+
+    let mut stream = TcpStream::connect(address).unwrap();
+    let start = Instant::now();
+    transfer(&mut stream, file, FILE_SIZE as u64).unwrap();
+    drop(stream);
+    assert_eq!(drain.join().unwrap(), FILE_SIZE);
+    start.elapsed()
+}
+
+fn report(name: &str, file: &mut File, transfer: fn(&mut TcpStream, &mut File, u64) -> io::Result<()>) {
+    // warm the page cache so both paths read from memory
+    time_transfer(file, transfer);
+
+    let total: Duration = (0..ITERATIONS).map(|_| time_transfer(file, transfer)).sum();
+    let seconds = total.as_secs_f64() / ITERATIONS as f64;
+    let throughput = FILE_SIZE as f64 / seconds / (1024.0 * 1024.0);
+    println!("{:<10} {:>8.2} ms/transfer {:>10.1} MiB/s", name, seconds * 1000.0, throughput);
+}
+
+fn main() {
+    let path = std::env::temp_dir().join(format!("http_server_bench_{}", std::process::id()));
+    let contents: Vec<u8> = (0..FILE_SIZE).map(|i| (i % 251) as u8).collect();
+    std::fs::write(&path, contents).unwrap();
+    let mut file = File::open(&path).unwrap();
+
+    println!("sending {} MiB over a local socket, {} iterations", FILE_SIZE / (1024 * 1024), ITERATIONS);
+    report("sendfile", &mut file, |stream, file, length| send_file(stream, file, 0, length));
+    report("buffered", &mut file, |stream, file, length| copy_buffered(stream, file, 0, length));
+
+    std::fs::remove_file(path).unwrap();
+}
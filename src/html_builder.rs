@@ -5,6 +5,12 @@ pub struct Page {
     html_head: HtmlElement,
 }
 
+impl Default for Page {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Page {
     pub fn new() -> Self {
         let mut html_head = HtmlElement::new("html", None);
@@ -84,8 +90,8 @@ impl Page {
         }
         
         if setup_webgl {
-            self.add_script(MimeType::JavaScript, Some("gl-matrix-min.js"), None);
-            self.add_script(MimeType::JavaScript, Some("webgl_test.js"), None);
+            self.add_script(MimeType::JAVASCRIPT, Some("gl-matrix-min.js"), None);
+            self.add_script(MimeType::JAVASCRIPT, Some("webgl_test.js"), None);
 
             element.add_attribute("class", "webgl_canvas");
         }
@@ -122,7 +128,7 @@ impl HtmlElement {
             tag_name: tag_name.to_string(),
             children: Vec::new(),
             attributes: Vec::new(),
-            content: content.and_then(|inner| Some(inner.to_string())),
+            content: content.map(|inner| inner.to_string()),
             open_closed: true,
         }
     }
@@ -154,7 +160,7 @@ impl HtmlElement {
     pub fn print_node_tree(&self, indent: usize) -> String {
         let mut result = String::new();
         for _ in 0..indent {
-            result.push_str("\t");
+            result.push('\t');
         }
         result.push_str(&format!("<{}", self.tag_name));
         for attribute in &self.attributes {
@@ -164,14 +170,14 @@ impl HtmlElement {
             result.push_str("/>\n");
             return result;
         }
-        result.push_str(">");
+        result.push('>');
         if !self.children.is_empty() || self.content.is_some() {
-            result.push_str("\n");
+            result.push('\n');
         }
         if let Some(content) = &self.content {
             for line in content.lines() {
                 for _ in 0..indent + 1 {
-                    result.push_str("\t");
+                    result.push('\t');
                 }
                 result.push_str(line);    
             }
@@ -180,9 +186,9 @@ impl HtmlElement {
             result.push_str(&child.print_node_tree(indent + 1));
         }
         if !self.children.is_empty() || self.content.is_some() {
-            result.push_str("\n");
+            result.push('\n');
             for _ in 0..indent {
-                result.push_str("\t");
+                result.push('\t');
             }
         }
         result.push_str(&format!("</{}>\n", self.tag_name));
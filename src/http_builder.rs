@@ -1,51 +1,202 @@
 use std::str::FromStr;
 use std::fmt::{Formatter, self};
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::time::SystemTime;
 use crate::server::util::mime_types::MimeType;
+use crate::server::util::precompressed::ContentCoding;
 
-// Define an enum for the status code and message
-#[derive(Debug, Clone)]
-pub enum HttpStatus {
-    RequestOk,  // 200
-    Created,  // 201
-    Accepted,  // 202
-    NoContent,  // 204
-    BadRequest,  // 400
-    Forbidden,  // 403
-    NotFound,  // 404
-    InternalServerError,  // 500
-    // Add more status codes as needed
+pub mod compression;
+pub mod date;
+pub mod negotiation;
+pub mod parser;
+pub mod streaming;
+
+use compression::{compress, negotiated_coding, vary_on_accept_encoding, varies_on_encoding, CompressionPolicy};
+use date::{format_http_date, http_date_now, parse_http_date};
+use parser::BodyFraming;
+use streaming::{streaming_framing, BodyStream};
+use negotiation::{parse_accept, parse_accept_charset, parse_accept_encoding, parse_accept_language, Preferences, QualityItem};
+
+/// Declares `HttpStatus` along with the numeric code and reason phrase of every variant
+macro_rules! http_statuses {
+    ($($variant:ident = $code:literal, $reason:literal;)*) => {
+        /// HTTP response status, covering every code in the IANA registry
+        ///
+        /// codes that aren't registered can still be sent with `HttpStatus::Custom`
+        #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+        pub enum HttpStatus {
+            $(
+                #[doc = concat!(stringify!($code), " ", $reason)]
+                $variant,
+            )*
+            /// Any other status code, with its own reason phrase
+            Custom(u16, String),
+        }
+
+        impl HttpStatus {
+            /// The numeric status code, e.g. 404
+            pub fn code(&self) -> u16 {
+                match self {
+                    $(HttpStatus::$variant => $code,)*
+                    HttpStatus::Custom(code, _) => *code,
+                }
+            }
+
+            /// The reason phrase sent after the code, e.g. "Not Found"
+            pub fn reason_phrase(&self) -> &str {
+                match self {
+                    $(HttpStatus::$variant => $reason,)*
+                    HttpStatus::Custom(_, reason) => reason,
+                }
+            }
+
+            /// The registered status for a code, None for codes without a variant
+            pub fn from_code(code: u16) -> Option<Self> {
+                match code {
+                    $($code => Some(HttpStatus::$variant),)*
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+http_statuses! {
+    Continue = 100, "Continue";
+    SwitchingProtocols = 101, "Switching Protocols";
+    Processing = 102, "Processing";
+    EarlyHints = 103, "Early Hints";
+
+    RequestOk = 200, "OK";
+    Created = 201, "Created";
+    Accepted = 202, "Accepted";
+    NonAuthoritativeInformation = 203, "Non-Authoritative Information";
+    NoContent = 204, "No Content";
+    ResetContent = 205, "Reset Content";
+    PartialContent = 206, "Partial Content";
+    MultiStatus = 207, "Multi-Status";
+    AlreadyReported = 208, "Already Reported";
+    ImUsed = 226, "IM Used";
+
+    MultipleChoices = 300, "Multiple Choices";
+    MovedPermanently = 301, "Moved Permanently";
+    Found = 302, "Found";
+    SeeOther = 303, "See Other";
+    NotModified = 304, "Not Modified";
+    UseProxy = 305, "Use Proxy";
+    TemporaryRedirect = 307, "Temporary Redirect";
+    PermanentRedirect = 308, "Permanent Redirect";
+
+    BadRequest = 400, "Bad Request";
+    Unauthorized = 401, "Unauthorized";
+    PaymentRequired = 402, "Payment Required";
+    Forbidden = 403, "Forbidden";
+    NotFound = 404, "Not Found";
+    MethodNotAllowed = 405, "Method Not Allowed";
+    NotAcceptable = 406, "Not Acceptable";
+    ProxyAuthenticationRequired = 407, "Proxy Authentication Required";
+    RequestTimeout = 408, "Request Timeout";
+    Conflict = 409, "Conflict";
+    Gone = 410, "Gone";
+    LengthRequired = 411, "Length Required";
+    PreconditionFailed = 412, "Precondition Failed";
+    ContentTooLarge = 413, "Content Too Large";
+    UriTooLong = 414, "URI Too Long";
+    UnsupportedMediaType = 415, "Unsupported Media Type";
+    RangeNotSatisfiable = 416, "Range Not Satisfiable";
+    ExpectationFailed = 417, "Expectation Failed";
+    MisdirectedRequest = 421, "Misdirected Request";
+    UnprocessableContent = 422, "Unprocessable Content";
+    Locked = 423, "Locked";
+    FailedDependency = 424, "Failed Dependency";
+    TooEarly = 425, "Too Early";
+    UpgradeRequired = 426, "Upgrade Required";
+    PreconditionRequired = 428, "Precondition Required";
+    TooManyRequests = 429, "Too Many Requests";
+    RequestHeaderFieldsTooLarge = 431, "Request Header Fields Too Large";
+    UnavailableForLegalReasons = 451, "Unavailable For Legal Reasons";
+
+    InternalServerError = 500, "Internal Server Error";
+    NotImplemented = 501, "Not Implemented";
+    BadGateway = 502, "Bad Gateway";
+    ServiceUnavailable = 503, "Service Unavailable";
+    GatewayTimeout = 504, "Gateway Timeout";
+    HttpVersionNotSupported = 505, "HTTP Version Not Supported";
+    VariantAlsoNegotiates = 506, "Variant Also Negotiates";
+    InsufficientStorage = 507, "Insufficient Storage";
+    LoopDetected = 508, "Loop Detected";
+    NotExtended = 510, "Not Extended";
+    NetworkAuthenticationRequired = 511, "Network Authentication Required";
+}
+
+impl HttpStatus {
+    /// A status for any code, registered codes get their variant and unregistered ones
+    /// a `Custom` status with an empty reason phrase
+    pub fn from_code_or_custom(code: u16) -> Self {
+        Self::from_code(code).unwrap_or(HttpStatus::Custom(code, String::new()))
+    }
+
+    /// 1xx
+    pub fn is_informational(&self) -> bool {
+        (100..200).contains(&self.code())
+    }
+
+    /// 2xx
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.code())
+    }
+
+    /// 3xx
+    pub fn is_redirect(&self) -> bool {
+        (300..400).contains(&self.code())
+    }
+
+    /// 4xx
+    pub fn is_client_error(&self) -> bool {
+        (400..500).contains(&self.code())
+    }
+
+    /// 5xx
+    pub fn is_server_error(&self) -> bool {
+        (500..600).contains(&self.code())
+    }
+
+    /// 4xx or 5xx
+    pub fn is_error(&self) -> bool {
+        self.is_client_error() || self.is_server_error()
+    }
 }
+
 impl FromStr for HttpStatus {
     type Err = ();
 
+    /// Parses either a bare code (`"404"`), a status with its reason phrase (`"404 Not Found"`)
+    /// or a whole status line (`"HTTP/1.1 404 Not Found"`), whose version is skipped
+    ///
+    /// registered codes always give their variant whatever the phrase says, since the reason
+    /// phrase carries no meaning, anything else in 100..=999 becomes `Custom`
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
-            "200 OK" => Ok(HttpStatus::RequestOk),
-            "201 Created" => Ok(HttpStatus::Created),
-            "202 Accepted" => Ok(HttpStatus::Accepted),
-            "204 No Content" => Ok(HttpStatus::NoContent),
-            "400 Bad Request" => Ok(HttpStatus::BadRequest),
-            "403 Forbidden" => Ok(HttpStatus::Forbidden),
-            "404 Not Found" => Ok(HttpStatus::NotFound),
-            "500 Internal Server Error" => Ok(HttpStatus::InternalServerError),
-            _ => Err(()),
+        let s = s.trim();
+        let s = match s.split_once(' ') {
+            Some((version, status)) if version.starts_with("HTTP/") => status.trim_start(),
+            _ => s,
+        };
+        let (code, reason) = s.split_once(' ').unwrap_or((s, ""));
+        if code.len() != 3 || !code.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(());
+        }
+        let code = code.parse::<u16>().map_err(|_| ())?;
+        if code < 100 {
+            return Err(());
         }
+        Ok(Self::from_code(code).unwrap_or_else(|| HttpStatus::Custom(code, reason.trim().to_string())))
     }
 }
 
 impl fmt::Display for HttpStatus {
-    // Returns the status code and message as a tuple
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        match self {
-            HttpStatus::RequestOk => write!(f, "200 OK"),
-            HttpStatus::Created => write!(f, "201 Created"),
-            HttpStatus::NotFound => write!(f, "404 Not Found"),
-            HttpStatus::Forbidden => write!(f, "403 Forbidden"),
-            HttpStatus::BadRequest => write!(f, "400 Bad Request"),
-            HttpStatus::NoContent => write!(f, "204 No Content"),
-            HttpStatus::InternalServerError => write!(f, "500 Internal Server Error"),
-            _ => write!(f, "Not Implemented!"),
-        }
+        write!(f, "{} {}", self.code(), self.reason_phrase())
     }
 }
 
@@ -99,35 +250,104 @@ impl fmt::Display for HttpMethod {
     }
 }
 
+/// The HTTP versions the server speaks
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum HttpVersion {
+    Http10,
+    #[default]
+    Http11,
+}
+
+impl HttpVersion {
+    /// Whether a connection stays open after a message when no Connection header says otherwise
+    pub fn is_persistent_by_default(&self) -> bool {
+        *self == HttpVersion::Http11
+    }
+}
+
+impl FromStr for HttpVersion {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "HTTP/1.0" => Ok(HttpVersion::Http10),
+            "HTTP/1.1" => Ok(HttpVersion::Http11),
+            _ => Err(()),
+        }
+    }
+}
+
+impl fmt::Display for HttpVersion {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            HttpVersion::Http10 => write!(f, "HTTP/1.0"),
+            HttpVersion::Http11 => write!(f, "HTTP/1.1"),
+        }
+    }
+}
+
 /// Enum to represent specific header types.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum HttpHeader {
     ContentType(MimeType),
     ContentLength(u64),
     Host(String),
-    Accept(Vec<MimeType>),
-    AcceptLanguage(String),
+    /// Media ranges, best first
+    Accept(Vec<QualityItem<MimeType>>),
+    /// Language ranges, best first
+    AcceptLanguage(Vec<QualityItem<String>>),
+    AcceptCharset(Vec<QualityItem<String>>),
+    AcceptEncoding(Vec<QualityItem<String>>),
     Connection(bool),
+    Date(SystemTime),
+    LastModified(SystemTime),
+    Expires(SystemTime),
     Custom(String, String), // For headers not explicitly listed here.
 }
 
-impl fmt::Display for HttpHeader {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+impl HttpHeader {
+    /// The header field name as it is written on the wire
+    pub fn name(&self) -> &str {
         match self {
-            HttpHeader::ContentType(content_type) => write!(f, "Content-Type: {}\r\n", content_type),
-            HttpHeader::ContentLength(content_length) => write!(f, "Content-Length: {}\r\n", content_length),
-            HttpHeader::Host(host) => write!(f, "Host: {}\r\n", host),
-            HttpHeader::Accept(accept) => write!(f, "Accept: {}\r\n", 
-                accept.iter()
-                    .map(|mime| mime.to_string())
-                    .collect::<Vec<String>>()
-                    .join(",")
-            ),
-            HttpHeader::AcceptLanguage(accept_language) => write!(f, "Accept-Language: {}\r\n", accept_language),
-            HttpHeader::Connection(connection) => write!(f, "Connection: {}\r\n", if *connection { "keep-alive" } else { "close" }),
-            HttpHeader::Custom(name, value) => write!(f, "{}: {}\r\n", name, value),
+            HttpHeader::ContentType(_) => "Content-Type",
+            HttpHeader::ContentLength(_) => "Content-Length",
+            HttpHeader::Host(_) => "Host",
+            HttpHeader::Accept(_) => "Accept",
+            HttpHeader::AcceptLanguage(_) => "Accept-Language",
+            HttpHeader::AcceptCharset(_) => "Accept-Charset",
+            HttpHeader::AcceptEncoding(_) => "Accept-Encoding",
+            HttpHeader::Connection(_) => "Connection",
+            HttpHeader::Date(_) => "Date",
+            HttpHeader::LastModified(_) => "Last-Modified",
+            HttpHeader::Expires(_) => "Expires",
+            HttpHeader::Custom(name, _) => name,
         }
     }
+
+    /// The header field value as it is written on the wire
+    pub fn value(&self) -> String {
+        match self {
+            HttpHeader::ContentType(content_type) => content_type.to_string(),
+            HttpHeader::ContentLength(content_length) => content_length.to_string(),
+            HttpHeader::Host(host) => host.clone(),
+            HttpHeader::Accept(accept) => join_quality_list(accept),
+            HttpHeader::AcceptLanguage(items) | HttpHeader::AcceptCharset(items) | HttpHeader::AcceptEncoding(items) =>
+                join_quality_list(items),
+            HttpHeader::Connection(connection) => if *connection { "keep-alive" } else { "close" }.to_string(),
+            HttpHeader::Date(time) | HttpHeader::LastModified(time) | HttpHeader::Expires(time) => format_http_date(*time),
+            HttpHeader::Custom(_, value) => value.clone(),
+        }
+    }
+}
+
+fn join_quality_list<T: fmt::Display>(items: &[QualityItem<T>]) -> String {
+    items.iter().map(|item| item.to_string()).collect::<Vec<String>>().join(", ")
+}
+
+impl fmt::Display for HttpHeader {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}\r\n", self.name(), self.value())
+    }
 }
 
 impl FromStr for HttpHeader {
@@ -142,27 +362,268 @@ impl FromStr for HttpHeader {
         let name = parts[0].trim();
         let value = parts[1].trim();
 
-        match name {
-            "Content-Type" => {
-                Ok(HttpHeader::ContentType(MimeType::from_str(value).unwrap_or(MimeType::PlainText)))
-            },
-            "Content-Length" => value.parse::<u64>()
+        // field names are case-insensitive
+        match name.to_ascii_lowercase().as_str() {
+            // a malformed type is kept as it is rather than guessed at
+            "content-type" => Ok(MimeType::from_str(value)
+                .map(HttpHeader::ContentType)
+                .unwrap_or_else(|_| HttpHeader::Custom(name.to_string(), value.to_string()))),
+            // only digits, `u64::from_str` would also take a leading '+'
+            "content-length" => Some(value)
+                .filter(|value| !value.is_empty() && value.bytes().all(|b| b.is_ascii_digit()))
+                .and_then(|value| value.parse::<u64>().ok())
                 .map(HttpHeader::ContentLength)
-                .map_err(|_| "Invalid Content-Length value".to_string()),
-            "Host" => Ok(HttpHeader::Host(value.to_string())),
-            "Accept" => Ok(HttpHeader::Accept(value.split(',').map(|s| MimeType::from_str(s.trim()).unwrap_or(MimeType::PlainText)).collect())),
-            "Accept-Language" => Ok(HttpHeader::AcceptLanguage(value.to_string())),
-            "Connection" => Ok(HttpHeader::Connection(value.to_lowercase() == "keep-alive")),
+                .ok_or_else(|| "Invalid Content-Length value".to_string()),
+            "host" => Ok(HttpHeader::Host(value.to_string())),
+            // malformed lists are kept as they are rather than lost
+            "accept" => Ok(parse_accept(value)
+                .map(HttpHeader::Accept)
+                .unwrap_or_else(|| HttpHeader::Custom(name.to_string(), value.to_string()))),
+            "accept-language" => Ok(parse_accept_language(value)
+                .map(HttpHeader::AcceptLanguage)
+                .unwrap_or_else(|| HttpHeader::Custom(name.to_string(), value.to_string()))),
+            "accept-charset" => Ok(parse_accept_charset(value)
+                .map(HttpHeader::AcceptCharset)
+                .unwrap_or_else(|| HttpHeader::Custom(name.to_string(), value.to_string()))),
+            "accept-encoding" => Ok(parse_accept_encoding(value)
+                .map(HttpHeader::AcceptEncoding)
+                .unwrap_or_else(|| HttpHeader::Custom(name.to_string(), value.to_string()))),
+            // other connection options, e.g. `Upgrade` or a list of several, are kept as they are
+            "connection" => Ok(match value.trim().to_ascii_lowercase().as_str() {
+                "keep-alive" => HttpHeader::Connection(true),
+                "close" => HttpHeader::Connection(false),
+                _ => HttpHeader::Custom(name.to_string(), value.to_string()),
+            }),
+            // invalid dates are kept as they are, e.g. `Expires: 0` has to be read as already expired
+            "date" | "last-modified" | "expires" => Ok(match (parse_http_date(value), name.to_ascii_lowercase().as_str()) {
+                (Some(time), "date") => HttpHeader::Date(time),
+                (Some(time), "last-modified") => HttpHeader::LastModified(time),
+                (Some(time), _) => HttpHeader::Expires(time),
+                (None, _) => HttpHeader::Custom(name.to_string(), value.to_string()),
+            }),
             _ => Ok(HttpHeader::Custom(name.to_string(), value.to_string())),
         }
     }
 }
 
+/// Ordered collection of headers with case-insensitive lookup
+///
+/// a name may appear several times, e.g. `Set-Cookie` or a list header split over several lines,
+/// and iteration yields the headers in the order they were added
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HttpHeaders {
+    headers: Vec<HttpHeader>,
+}
+
+impl HttpHeaders {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends a header, keeping any others with the same name
+    pub fn add(&mut self, header: HttpHeader) {
+        self.headers.push(header);
+    }
+
+    /// Replaces every header with the same name by this one
+    pub fn set(&mut self, header: HttpHeader) {
+        let name = header.name().to_string();
+        self.remove(&name);
+        self.headers.push(header);
+    }
+
+    /// Removes every header with the given name, returning how many were removed
+    pub fn remove(&mut self, name: &str) -> usize {
+        let before = self.headers.len();
+        self.headers.retain(|header| !header.name().eq_ignore_ascii_case(name));
+        before - self.headers.len()
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.find(name).is_some()
+    }
+
+    /// The value of the first header with the given name
+    pub fn get(&self, name: &str) -> Option<String> {
+        self.find(name).map(|header| header.value())
+    }
+
+    /// The values of every header with the given name, in order
+    pub fn get_all(&self, name: &str) -> Vec<String> {
+        self.headers.iter()
+            .filter(|header| header.name().eq_ignore_ascii_case(name))
+            .map(|header| header.value())
+            .collect()
+    }
+
+    /// All the values of a list header (`Accept-Encoding: gzip, br`), whether it was sent
+    /// on one line or split over several
+    pub fn get_list(&self, name: &str) -> Vec<String> {
+        self.get_all(name).iter()
+            .flat_map(|value| value.split(','))
+            .map(|item| item.trim().to_string())
+            .filter(|item| !item.is_empty())
+            .collect()
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, HttpHeader> {
+        self.headers.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.headers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.headers.is_empty()
+    }
+
+    fn find(&self, name: &str) -> Option<&HttpHeader> {
+        self.headers.iter().find(|header| header.name().eq_ignore_ascii_case(name))
+    }
+
+    /// The first header with the given name parsed into its typed variant, even if it was added as `Custom`
+    fn typed(&self, name: &str) -> Option<HttpHeader> {
+        let header = self.find(name)?;
+        match header {
+            HttpHeader::Custom(name, value) => HttpHeader::from_str(&format!("{}: {}", name, value)).ok(),
+            typed => Some(typed.clone()),
+        }
+    }
+
+    /// Every header with the given name combined into one typed list header, as if sent on one line
+    fn typed_list(&self, name: &str) -> Option<HttpHeader> {
+        let values = self.get_all(name);
+        if values.is_empty() {
+            return None;
+        }
+        HttpHeader::from_str(&format!("{}: {}", name, values.join(", "))).ok()
+    }
+
+    pub fn content_type(&self) -> Option<MimeType> {
+        match self.typed("Content-Type")? {
+            HttpHeader::ContentType(content_type) => Some(content_type),
+            _ => None,
+        }
+    }
+
+    pub fn content_length(&self) -> Option<u64> {
+        match self.typed("Content-Length")? {
+            HttpHeader::ContentLength(content_length) => Some(content_length),
+            _ => None,
+        }
+    }
+
+    pub fn host(&self) -> Option<String> {
+        match self.typed("Host")? {
+            HttpHeader::Host(host) => Some(host),
+            _ => None,
+        }
+    }
+
+    pub fn accept(&self) -> Option<Vec<QualityItem<MimeType>>> {
+        match self.typed_list("Accept")? {
+            HttpHeader::Accept(accept) => Some(accept),
+            _ => None,
+        }
+    }
+
+    pub fn accept_language(&self) -> Option<Vec<QualityItem<String>>> {
+        match self.typed_list("Accept-Language")? {
+            HttpHeader::AcceptLanguage(accept_language) => Some(accept_language),
+            _ => None,
+        }
+    }
+
+    pub fn accept_charset(&self) -> Option<Vec<QualityItem<String>>> {
+        match self.typed_list("Accept-Charset")? {
+            HttpHeader::AcceptCharset(accept_charset) => Some(accept_charset),
+            _ => None,
+        }
+    }
+
+    pub fn accept_encoding(&self) -> Option<Vec<QualityItem<String>>> {
+        match self.typed_list("Accept-Encoding")? {
+            HttpHeader::AcceptEncoding(accept_encoding) => Some(accept_encoding),
+            _ => None,
+        }
+    }
+
+    /// Whether the connection should be kept alive, if the client said either way
+    pub fn connection(&self) -> Option<bool> {
+        match self.typed("Connection")? {
+            HttpHeader::Connection(keep_alive) => Some(keep_alive),
+            _ => None,
+        }
+    }
+
+    /// The first header with the given name as an HTTP-date in any of the accepted formats,
+    /// e.g. `If-Modified-Since` or `Retry-After`
+    pub fn get_date(&self, name: &str) -> Option<SystemTime> {
+        match self.find(name)? {
+            HttpHeader::Date(time) | HttpHeader::LastModified(time) | HttpHeader::Expires(time) => Some(*time),
+            header => parse_http_date(&header.value()),
+        }
+    }
+
+    pub fn date(&self) -> Option<SystemTime> {
+        self.get_date("Date")
+    }
+
+    pub fn last_modified(&self) -> Option<SystemTime> {
+        self.get_date("Last-Modified")
+    }
+
+    pub fn expires(&self) -> Option<SystemTime> {
+        self.get_date("Expires")
+    }
+}
+
+impl FromIterator<HttpHeader> for HttpHeaders {
+    fn from_iter<T: IntoIterator<Item = HttpHeader>>(iter: T) -> Self {
+        HttpHeaders { headers: iter.into_iter().collect() }
+    }
+}
+
+impl Extend<HttpHeader> for HttpHeaders {
+    fn extend<T: IntoIterator<Item = HttpHeader>>(&mut self, iter: T) {
+        self.headers.extend(iter);
+    }
+}
+
+impl IntoIterator for HttpHeaders {
+    type Item = HttpHeader;
+    type IntoIter = std::vec::IntoIter<HttpHeader>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.headers.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a HttpHeaders {
+    type Item = &'a HttpHeader;
+    type IntoIter = std::slice::Iter<'a, HttpHeader>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.headers.iter()
+    }
+}
+
+impl fmt::Display for HttpHeaders {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for header in &self.headers {
+            write!(f, "{}", header)?;
+        }
+        Ok(())
+    }
+}
+
 /// Structure to represent the first line of an HTTP request.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct HttpRequestLine {
     method: HttpMethod,
     uri: String,
+    version: HttpVersion,
 }
 
 impl HttpRequestLine {
@@ -170,119 +631,605 @@ impl HttpRequestLine {
         HttpRequestLine {
             method,
             uri: uri.to_string(),
+            version: HttpVersion::default(),
         }
     }
+
+    pub fn with_version(mut self, version: HttpVersion) -> Self {
+        self.version = version;
+        self
+    }
 }
 
 impl fmt::Display for HttpRequestLine {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{} {} HTTP/1.1\r\n", self.method, self.uri)
+        write!(f, "{} {} {}\r\n", self.method, self.uri, self.version)
     }
 }
 
 struct HttpResponseLine {
+    version: HttpVersion,
     status: HttpStatus
 }
 
-impl HttpResponseLine {
-    fn new(status: HttpStatus) -> Self {
-        HttpResponseLine {
-            status,
+impl fmt::Display for HttpResponseLine {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}\r\n", self.version, self.status)
+    }
+}
+
+/// Settings for the responses to a request
+///
+/// the server works one out for each request it reads, from its own settings and what the request
+/// asks for, and keeps it with the request, responses built with `HttpResponse::for_request` use it
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ResponseContext {
+    /// Version written in the status line
+    pub version: HttpVersion,
+    /// Send `Connection: close`, for when the client expects the connection to stay open but it won't
+    pub close_connection: bool,
+    /// Send a `Date` header with the time the response was built
+    pub send_date: bool,
+    /// Headers sent with every response that doesn't set them itself, e.g. `Server`
+    pub default_headers: Vec<HttpHeader>,
+    /// Compress bodies on the fly, `None` leaves every response as it is
+    pub compression: Option<CompressionPolicy>,
+    /// The coding the request being answered accepts, from its Accept-Encoding
+    pub content_coding: Option<ContentCoding>,
+}
+
+/// The body of an `HttpResponse`
+pub enum HttpBody {
+    Empty,
+    Bytes(Vec<u8>),
+    /// A file sent from its current position, `length` bytes long
+    File(File, u64),
+    /// Anything readable, sent until it ends, with a length if it is known up front,
+    /// without one it is streamed like a body from `HttpResponse::stream`
+    Reader(Box<dyn Read + Send>, Option<u64>),
+}
+
+impl HttpBody {
+    /// The length of the body, if known before it is sent
+    pub fn len(&self) -> Option<u64> {
+        match self {
+            HttpBody::Empty => Some(0),
+            HttpBody::Bytes(bytes) => Some(bytes.len() as u64),
+            HttpBody::File(_, length) => Some(*length),
+            HttpBody::Reader(_, length) => *length,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == Some(0)
+    }
+
+    /// Writes the whole body out
+    pub fn write_to<W: Write>(self, writer: &mut W) -> io::Result<()> {
+        match self {
+            HttpBody::Empty => Ok(()),
+            HttpBody::Bytes(bytes) => writer.write_all(&bytes),
+            HttpBody::File(file, length) => io::copy(&mut file.take(length), writer).map(|_| ()),
+            HttpBody::Reader(mut reader, _) => io::copy(&mut reader, writer).map(|_| ()),
         }
     }
 }
 
-impl fmt::Display for HttpResponseLine {
+impl fmt::Debug for HttpBody {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "HTTP/1.1 {}\r\n", self.status)
+        match self {
+            HttpBody::Empty => write!(f, "Empty"),
+            HttpBody::Bytes(bytes) => write!(f, "Bytes({} bytes)", bytes.len()),
+            HttpBody::File(_, length) => write!(f, "File({} bytes)", length),
+            HttpBody::Reader(_, length) => write!(f, "Reader({:?})", length),
+        }
     }
 }
 
+/// A complete HTTP response, status line, headers and body
+///
+/// Built fluently, the body setters fill in Content-Type and Content-Length:
+/// `HttpResponse::new(RequestOk).header(...).html(page)`, then sent with `write_to`.
+/// `Display` gives just the status line and headers.
 pub struct HttpResponse {
     response_line: HttpResponseLine,
-    headers: Vec<HttpHeader>,
+    headers: HttpHeaders,
+    /// Headers from the `ResponseContext`, only sent if not set on the response itself
+    defaults: HttpHeaders,
+    body: HttpBody,
+    /// Decides whether the body is compressed when it is written
+    context: ResponseContext,
 }
 
 impl HttpResponse {
+    /// A plain HTTP/1.1 response, with none of the server's settings
     pub fn new(status: HttpStatus) -> Self {
+        Self::with_context(status, &ResponseContext::default())
+    }
+
+    /// A response to `request`, with the settings the server gave it
+    pub fn for_request(status: HttpStatus, request: &HttpRequest) -> Self {
+        Self::with_context(status, request.response_context())
+    }
+
+    pub fn with_context(status: HttpStatus, context: &ResponseContext) -> Self {
+        let mut headers = HttpHeaders::new();
+        if context.close_connection {
+            headers.add(HttpHeader::Connection(false));
+        }
+        let mut defaults = HttpHeaders::new();
+        if context.send_date {
+            defaults.add(HttpHeader::Custom("Date".to_string(), http_date_now()));
+        }
+        defaults.extend(context.default_headers.iter().cloned());
         HttpResponse {
-            response_line: HttpResponseLine::new(status),
-            headers: Vec::new(),
+            response_line: HttpResponseLine { version: context.version, status },
+            headers,
+            defaults,
+            body: HttpBody::Empty,
+            context: context.clone(),
         }
     }
 
     pub fn add_header(&mut self, header: HttpHeader) {
-        self.headers.push(header);
+        self.headers.add(header);
+    }
+
+    pub fn get_status(&self) -> &HttpStatus {
+        &self.response_line.status
+    }
+
+    pub fn body(&self) -> &HttpBody {
+        &self.body
+    }
+
+    /// Sets the version written in the status line
+    pub fn version(mut self, version: HttpVersion) -> Self {
+        self.response_line.version = version;
+        self
+    }
+
+    /// Sets the status
+    pub fn status(mut self, status: HttpStatus) -> Self {
+        self.response_line.status = status;
+        self
+    }
+
+    /// Adds a header, keeping any others with the same name
+    pub fn header(mut self, header: HttpHeader) -> Self {
+        self.headers.add(header);
+        self
+    }
+
+    /// Sets the body to raw bytes of the given type
+    pub fn bytes(self, body: Vec<u8>, content_type: MimeType) -> Self {
+        self.with_body(HttpBody::Bytes(body), HttpHeader::ContentType(content_type))
+    }
+
+    /// Sets an HTML body
+    pub fn html(self, body: impl Into<String>) -> Self {
+        self.bytes(body.into().into_bytes(), MimeType::HTML)
+    }
+
+    /// Sets a JSON body, the string should already be serialized JSON
+    pub fn json(self, body: impl Into<String>) -> Self {
+        self.bytes(body.into().into_bytes(), MimeType::JSON)
+    }
+
+    /// Sets a plain text body
+    pub fn text(self, body: impl Into<String>) -> Self {
+        self.bytes(body.into().into_bytes(), MimeType::PLAIN_TEXT)
+    }
+
+    /// Sets the body to the rest of a file, its length is taken from the file's metadata
+    pub fn file(self, file: File, content_type: MimeType) -> io::Result<Self> {
+        let length = file.metadata()?.len();
+        Ok(self.with_body(HttpBody::File(file, length), HttpHeader::ContentType(content_type)))
+    }
+
+    /// Streams the body from a reader, without a length the body ends when the connection closes
+    pub fn reader(self, reader: impl Read + Send + 'static, content_type: MimeType, length: Option<u64>) -> Self {
+        self.with_body(HttpBody::Reader(Box::new(reader), length), HttpHeader::ContentType(content_type))
+    }
+
+    fn with_body(mut self, body: HttpBody, content_type: HttpHeader) -> Self {
+        self.headers.set(content_type);
+        match body.len() {
+            Some(length) => self.headers.set(HttpHeader::ContentLength(length)),
+            None => { self.headers.remove("Content-Length"); }
+        }
+        self.body = body;
+        self
+    }
+
+    /// Serializes the whole response, head then body, to any writer
+    pub fn write_to<W: Write>(mut self, writer: &mut W) -> io::Result<()> {
+        self.compress_body();
+        if let HttpBody::Reader(_, None) = self.body {
+            let HttpBody::Reader(mut reader, _) = std::mem::replace(&mut self.body, HttpBody::Empty) else { unreachable!() };
+            let mut body = self.start_stream(writer)?;
+            io::copy(&mut reader, &mut body)?;
+            return body.finish();
+        }
+        self.write_head_to(writer)?;
+        self.body.write_to(writer)?;
+        writer.flush()
+    }
+
+    /// Writes the head and hands back the body to be sent a piece at a time as it is produced,
+    /// chunked for HTTP/1.1 clients and delimited by closing the connection for HTTP/1.0 ones
+    ///
+    /// trailers sent with `BodyStream::finish_with_trailers` ought to be announced with a `Trailer` header
+    pub fn stream<W: Write>(mut self, content_type: MimeType, writer: W) -> io::Result<BodyStream<W>> {
+        self.headers.set(HttpHeader::ContentType(content_type));
+        self.body = HttpBody::Empty;
+        self.start_stream(writer)
+    }
+
+    fn start_stream<W: Write>(mut self, mut writer: W) -> io::Result<BodyStream<W>> {
+        self.headers.remove("Content-Length");
+        let framing = streaming_framing(self.response_line.version);
+        match framing {
+            BodyFraming::Chunked => self.headers.set(HttpHeader::Custom("Transfer-Encoding".to_string(), "chunked".to_string())),
+            _ => self.headers.set(HttpHeader::Connection(false)),
+        }
+        self.write_head_to(&mut writer)?;
+        Ok(BodyStream::new(writer, framing))
+    }
+
+    /// Compresses an in-memory body when the server's compression policy and the request's
+    /// Accept-Encoding allow it, and the result actually comes out smaller
+    fn compress_body(&mut self) {
+        let HttpBody::Bytes(body) = &self.body else { return };
+        let status = self.response_line.status.code();
+        if self.response_line.status.is_informational() || matches!(status, 204 | 206 | 304) || self.headers.contains("Content-Encoding") {
+            return;
+        }
+        let Some(content_type) = self.headers.content_type() else { return };
+        let length = body.len() as u64;
+        if varies_on_encoding(&self.context, &content_type, length) {
+            vary_on_accept_encoding(&mut self.headers);
+        }
+        let Some(coding) = negotiated_coding(&self.context, &content_type, length) else { return };
+        let Some(compressed) = compress(coding, body).filter(|compressed| compressed.len() < body.len()) else { return };
+
+        self.headers.set(HttpHeader::Custom("Content-Encoding".to_string(), coding.token().to_string()));
+        if let Some(etag) = self.headers.get("ETag").filter(|etag| etag.starts_with('"')) {
+            let tagged = format!("{}-{}\"", etag.trim_end_matches('"'), coding.token());
+            self.headers.set(HttpHeader::Custom("ETag".to_string(), tagged));
+        }
+        self.headers.set(HttpHeader::ContentLength(compressed.len() as u64));
+        self.body = HttpBody::Bytes(compressed);
+    }
+
+    /// Writes just the status line and headers, for bodies the caller streams itself
+    pub fn write_head_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(self.to_string().as_bytes())
+    }
+
+    pub fn headers(&self) -> &HttpHeaders {
+        &self.headers
+    }
+
+    pub fn headers_mut(&mut self) -> &mut HttpHeaders {
+        &mut self.headers
     }
 }
 
 impl fmt::Display for HttpResponse {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        let mut headers_string = String::new();
-        for header in &self.headers {
-            headers_string.push_str(&format!("{}", header));
+        write!(f, "{}{}", self.response_line, self.headers)?;
+        for header in self.defaults.iter().filter(|header| !self.headers.contains(header.name())) {
+            write!(f, "{}", header)?;
         }
-        write!(f, "{}{}\r\n", self.response_line, headers_string)
+        write!(f, "\r\n")
     }
 }
 
-
 /// Structure to represent a complete HTTP request header.
 #[derive(Debug, Clone)]
 pub struct HttpRequest {
     request_line: HttpRequestLine,
-    headers: Vec<HttpHeader>,
+    headers: HttpHeaders,
+    body: Vec<u8>,
+    response_context: ResponseContext,
 }
 
 impl HttpRequest {
     pub fn new(request_line: HttpRequestLine) -> Self {
         HttpRequest {
             request_line,
-            headers: Vec::new(),
+            headers: HttpHeaders::new(),
+            body: Vec::new(),
+            response_context: ResponseContext::default(),
         }
     }
 
     /// Adds a header to the collection.
     pub fn add_header(&mut self, header: HttpHeader) {
-        self.headers.push(header);
+        self.headers.add(header);
+    }
+
+    pub fn headers(&self) -> &HttpHeaders {
+        &self.headers
     }
 
     pub fn path(&self) -> String {
-        return self.request_line.uri.clone();
+        self.request_line.uri.clone()
+    }
+
+    /// Finds the value of the first header with the given name, ignoring case
+    pub fn get_header(&self, name: &str) -> Option<String> {
+        self.headers.get(name)
     }
 
     pub fn get_host(&self) -> String {
-        for header in &self.headers {
-            if let HttpHeader::Host(host) = header {
-                return host.clone();
-            }
-        }
-        "localhost".to_string()
+        self.headers.host().unwrap_or("localhost".to_string())
     }
     
     pub fn method(&self) -> HttpMethod {
         self.request_line.method.clone()
     }
+
+    pub fn version(&self) -> HttpVersion {
+        self.request_line.version
+    }
+
+    /// What the client will accept, for picking between representations of the response
+    pub fn preferences(&self) -> Preferences {
+        Preferences::from_headers(self.headers())
+    }
+
+    /// Whether the client expects the connection to stay open after the response,
+    /// HTTP/1.1 connections are persistent unless closed, HTTP/1.0 ones only if asked for
+    pub fn keep_alive(&self) -> bool {
+        let options = self.headers.get_list("Connection");
+        if options.iter().any(|option| option.eq_ignore_ascii_case("close")) {
+            return false;
+        }
+        self.version().is_persistent_by_default()
+            || options.iter().any(|option| option.eq_ignore_ascii_case("keep-alive"))
+    }
+
+    /// The body, with any chunked framing already removed
+    pub fn body(&self) -> &[u8] {
+        &self.body
+    }
+
+    pub fn set_body(&mut self, body: Vec<u8>) {
+        self.body = body;
+    }
+
+    /// What responses to this request are built with, set by the server once it has read the head
+    pub fn response_context(&self) -> &ResponseContext {
+        &self.response_context
+    }
+
+    pub fn set_response_context(&mut self, context: ResponseContext) {
+        self.response_context = context;
+    }
 }
 
 impl fmt::Display for HttpRequest {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        let mut headers_string = String::new();
-        for header in &self.headers {
-            headers_string.push_str(&format!("{}", header));
-        }
-        write!(f, "{}{}\r\n", self.request_line, headers_string)
+        write!(f, "{}{}\r\n", self.request_line, self.headers)
     }
 }
 
-pub fn write_http_response_header(status: HttpStatus, content_type: Option<MimeType>, content_length: Option<u64>) -> String {
-    let mut response = HttpResponse::new(status);
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_header_from_str_is_case_insensitive() {
+        assert_eq!(HttpHeader::from_str("content-type: text/html"), Ok(HttpHeader::ContentType(MimeType::HTML)));
+        assert_eq!(HttpHeader::from_str("Content-Type: text/html; charset=UTF-8"),
+            Ok(HttpHeader::ContentType(MimeType::HTML.with_charset("UTF-8"))));
+        assert_eq!(HttpHeader::from_str("Content-Type: nonsense"),
+            Ok(HttpHeader::Custom("Content-Type".to_string(), "nonsense".to_string())));
+        assert_eq!(HttpHeader::from_str("CONTENT-LENGTH: 12"), Ok(HttpHeader::ContentLength(12)));
+        assert_eq!(HttpHeader::from_str("host:example.com"), Ok(HttpHeader::Host("example.com".to_string())));
+        assert_eq!(HttpHeader::from_str("X-Thing: a:b"), Ok(HttpHeader::Custom("X-Thing".to_string(), "a:b".to_string())));
+        assert_eq!(HttpHeader::from_str("Connection: Close"), Ok(HttpHeader::Connection(false)));
+        assert_eq!(HttpHeader::from_str("Connection: keep-alive, Upgrade"),
+            Ok(HttpHeader::Custom("Connection".to_string(), "keep-alive, Upgrade".to_string())));
+    }
+
+    #[test]
+    fn test_headers_lookup() {
+        let mut headers = HttpHeaders::new();
+        headers.add(HttpHeader::Host("example.com".to_string()));
+        headers.add(HttpHeader::Custom("Set-Cookie".to_string(), "a=1".to_string()));
+        headers.add(HttpHeader::Custom("set-cookie".to_string(), "b=2".to_string()));
+        headers.add(HttpHeader::Custom("Accept-Encoding".to_string(), "gzip, br".to_string()));
+        headers.add(HttpHeader::Custom("accept-encoding".to_string(), "deflate".to_string()));
+
+        assert_eq!(headers.get("HOST"), Some("example.com".to_string()));
+        assert_eq!(headers.get("SET-COOKIE"), Some("a=1".to_string()));
+        assert_eq!(headers.get_all("Set-Cookie"), vec!["a=1".to_string(), "b=2".to_string()]);
+        assert_eq!(headers.get_list("Accept-Encoding"), vec!["gzip", "br", "deflate"]);
+        assert_eq!(headers.get("Missing"), None);
+        assert!(headers.contains("accept-encoding"));
 
-    response.add_header(HttpHeader::ContentType(content_type.unwrap_or(MimeType::PlainText)));
+        let names: Vec<&str> = headers.iter().map(|header| header.name()).collect();
+        assert_eq!(names, vec!["Host", "Set-Cookie", "set-cookie", "Accept-Encoding", "accept-encoding"]);
+
+        assert_eq!(headers.remove("set-cookie"), 2);
+        headers.set(HttpHeader::Custom("ACCEPT-ENCODING".to_string(), "br".to_string()));
+        assert_eq!(headers.get_all("Accept-Encoding"), vec!["br".to_string()]);
+        assert_eq!(headers.len(), 2);
+    }
+
+    #[test]
+    fn test_headers_typed_getters() {
+        let headers: HttpHeaders = vec![
+            HttpHeader::Custom("content-type".to_string(), "application/json".to_string()),
+            HttpHeader::ContentLength(42),
+            HttpHeader::Custom("connection".to_string(), "Keep-Alive".to_string()),
+        ].into_iter().collect();
+
+        assert_eq!(headers.content_type(), Some(MimeType::JSON));
+        assert_eq!(headers.content_length(), Some(42));
+        assert_eq!(headers.connection(), Some(true));
+        assert_eq!(headers.host(), None);
+        assert_eq!(headers.to_string(), "content-type: application/json\r\nContent-Length: 42\r\nconnection: Keep-Alive\r\n");
+    }
 
-    if let Some(content_length) = content_length {
-        response.add_header(HttpHeader::ContentLength(content_length));
+    #[test]
+    fn test_response_builder() {
+        let mut output = Vec::new();
+        HttpResponse::new(HttpStatus::RequestOk)
+            .header(HttpHeader::Custom("X-Request-Id".to_string(), "7".to_string()))
+            .html("<p>hi</p>")
+            .write_to(&mut output)
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "HTTP/1.1 200 OK\r\nX-Request-Id: 7\r\nContent-Type: text/html\r\nContent-Length: 9\r\n\r\n<p>hi</p>"
+        );
     }
 
-    response.to_string()
-}
\ No newline at end of file
+    #[test]
+    fn test_response_builder_replaces_body() {
+        let response = HttpResponse::new(HttpStatus::RequestOk)
+            .text("first body")
+            .status(HttpStatus::Created)
+            .json("{}");
+        assert_eq!(response.get_status(), &HttpStatus::Created);
+        assert_eq!(response.headers().content_type(), Some(MimeType::JSON));
+        assert_eq!(response.headers().get_all("Content-Length"), vec!["2".to_string()]);
+        assert_eq!(response.body().len(), Some(2));
+    }
+
+    #[test]
+    fn test_response_reader_body() {
+        let mut output = Vec::new();
+        HttpResponse::new(HttpStatus::RequestOk)
+            .reader(io::Cursor::new(b"streamed".to_vec()), MimeType::PLAIN_TEXT, None)
+            .write_to(&mut output)
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nTransfer-Encoding: chunked\r\n\r\n8\r\nstreamed\r\n0\r\n\r\n"
+        );
+    }
+
+    #[test]
+    fn test_status_codes_and_reasons() {
+        assert_eq!(HttpStatus::RequestOk.code(), 200);
+        assert_eq!(HttpStatus::RequestOk.to_string(), "200 OK");
+        assert_eq!(HttpStatus::HttpVersionNotSupported.to_string(), "505 HTTP Version Not Supported");
+        assert_eq!(HttpStatus::Custom(299, "Custom Thing".to_string()).to_string(), "299 Custom Thing");
+        assert_eq!(HttpStatus::from_code(404), Some(HttpStatus::NotFound));
+        assert_eq!(HttpStatus::from_code(299), None);
+        assert_eq!(HttpStatus::from_code_or_custom(299), HttpStatus::Custom(299, String::new()));
+    }
+
+    #[test]
+    fn test_status_classification() {
+        assert!(HttpStatus::Continue.is_informational());
+        assert!(HttpStatus::NoContent.is_success());
+        assert!(HttpStatus::PermanentRedirect.is_redirect());
+        assert!(HttpStatus::TooManyRequests.is_client_error());
+        assert!(HttpStatus::BadGateway.is_server_error());
+        assert!(HttpStatus::NotFound.is_error() && HttpStatus::InternalServerError.is_error());
+        assert!(!HttpStatus::RequestOk.is_error());
+        assert!(HttpStatus::Custom(599, String::new()).is_server_error());
+    }
+
+    #[test]
+    fn test_status_from_str() {
+        assert_eq!("200 OK".parse(), Ok(HttpStatus::RequestOk));
+        assert_eq!("200".parse(), Ok(HttpStatus::RequestOk));
+        assert_eq!("404 Nope".parse(), Ok(HttpStatus::NotFound));
+        assert_eq!("299 Custom Thing".parse(), Ok(HttpStatus::Custom(299, "Custom Thing".to_string())));
+        assert_eq!("OK".parse::<HttpStatus>(), Err(()));
+        assert_eq!("20".parse::<HttpStatus>(), Err(()));
+        assert_eq!("1000".parse::<HttpStatus>(), Err(()));
+        assert_eq!("099".parse::<HttpStatus>(), Err(()));
+
+        assert_eq!("HTTP/1.1 404 Not Found".parse(), Ok(HttpStatus::NotFound));
+        assert_eq!("HTTP/1.0 299 Custom Thing\r\n".parse(), Ok(HttpStatus::Custom(299, "Custom Thing".to_string())));
+        assert_eq!("HTTP/1.1".parse::<HttpStatus>(), Err(()));
+        assert_eq!("HTTP/1.1 OK".parse::<HttpStatus>(), Err(()));
+    }
+
+    #[test]
+    fn test_status_round_trip() {
+        for code in 100..600 {
+            let status = HttpStatus::from_code_or_custom(code);
+            assert_eq!(status.to_string().parse(), Ok(status.clone()));
+            assert_eq!(code.to_string().parse::<HttpStatus>().map(|s| s.code()), Ok(code));
+            for version in [HttpVersion::Http10, HttpVersion::Http11] {
+                let line = HttpResponseLine { version, status: status.clone() };
+                assert_eq!(line.to_string().parse(), Ok(status.clone()));
+            }
+        }
+    }
+
+    #[test]
+    fn test_date_headers() {
+        let time = std::time::UNIX_EPOCH + std::time::Duration::from_secs(784111777);
+        assert_eq!(HttpHeader::from_str("last-modified: Sun, 06 Nov 1994 08:49:37 GMT"), Ok(HttpHeader::LastModified(time)));
+        assert_eq!(HttpHeader::Date(time).to_string(), "Date: Sun, 06 Nov 1994 08:49:37 GMT\r\n");
+        assert_eq!(HttpHeader::from_str("Expires: 0"), Ok(HttpHeader::Custom("Expires".to_string(), "0".to_string())));
+
+        let headers: HttpHeaders = [
+            HttpHeader::from_str("Expires: Sun Nov  6 08:49:37 1994").unwrap(),
+            HttpHeader::Custom("If-Modified-Since".to_string(), "Sunday, 06-Nov-94 08:49:37 GMT".to_string()),
+        ].into_iter().collect();
+        assert_eq!(headers.expires(), Some(time));
+        assert_eq!(headers.get_date("if-modified-since"), Some(time));
+        assert_eq!(headers.last_modified(), None);
+    }
+
+    #[test]
+    fn test_version_keep_alive() {
+        assert_eq!("HTTP/1.0".parse(), Ok(HttpVersion::Http10));
+        assert_eq!(HttpVersion::Http11.to_string(), "HTTP/1.1");
+        assert_eq!("HTTP/2".parse::<HttpVersion>(), Err(()));
+
+        let request = |version: HttpVersion, connection: Option<&str>| {
+            let mut request = HttpRequest::new(HttpRequestLine::new(HttpMethod::GET, "/").with_version(version));
+            if let Some(connection) = connection {
+                request.add_header(HttpHeader::from_str(&format!("Connection: {}", connection)).unwrap());
+            }
+            request
+        };
+        assert!(request(HttpVersion::Http11, None).keep_alive());
+        assert!(!request(HttpVersion::Http11, Some("close")).keep_alive());
+        assert!(!request(HttpVersion::Http10, None).keep_alive());
+        assert!(request(HttpVersion::Http10, Some("keep-alive")).keep_alive());
+    }
+
+    #[test]
+    fn test_response_context() {
+        let mut request = HttpRequest::new(HttpRequestLine::new(HttpMethod::GET, "/"));
+        request.set_response_context(ResponseContext { version: HttpVersion::Http10, close_connection: true, ..ResponseContext::default() });
+        let head = HttpResponse::for_request(HttpStatus::RequestOk, &request).to_string();
+        assert_eq!(head, "HTTP/1.0 200 OK\r\nConnection: close\r\n\r\n");
+        assert!(HttpResponse::new(HttpStatus::RequestOk).to_string().starts_with("HTTP/1.1 200 OK\r\n"));
+    }
+
+    #[test]
+    fn test_default_headers() {
+        let context = ResponseContext {
+            send_date: true,
+            default_headers: vec![
+                HttpHeader::Custom("Server".to_string(), "test".to_string()),
+                HttpHeader::Custom("X-Frame-Options".to_string(), "DENY".to_string()),
+            ],
+            ..ResponseContext::default()
+        };
+        let response = HttpResponse::with_context(HttpStatus::RequestOk, &context)
+            .header(HttpHeader::Custom("X-Frame-Options".to_string(), "SAMEORIGIN".to_string()));
+
+        let head = response.to_string();
+        let date = head.lines().find_map(|line| line.strip_prefix("Date: ")).unwrap();
+        assert!(date::parse_http_date(date).is_some());
+        assert!(head.contains("Server: test\r\n"));
+        //set on the response itself, so the default isn't sent
+        assert!(head.contains("X-Frame-Options: SAMEORIGIN\r\n"));
+        assert!(!head.contains("DENY"));
+    }
+}
@@ -0,0 +1,170 @@
+use crate::http_builder::negotiation::{encoding_quality, QualityItem};
+use crate::http_builder::{HttpHeader, HttpHeaders, ResponseContext};
+use crate::server::util::deflate::{gzip, zlib};
+use crate::server::util::mime_types::MimeType;
+use crate::server::util::precompressed::ContentCoding;
+
+/// Bodies smaller than this aren't worth compressing, gzip's framing alone is 18 bytes
+const DEFAULT_MIN_SIZE: u64 = 1024;
+
+/// Files are read into memory to be compressed, larger ones are sent as they are
+const DEFAULT_MAX_SIZE: u64 = 8 * 1024 * 1024;
+
+/// The codings the server can produce itself, in order of preference
+const CODINGS: [ContentCoding; 2] = [ContentCoding::Gzip, ContentCoding::Deflate];
+
+/// Which responses are compressed on the fly, for clients whose `Accept-Encoding` allows it
+///
+/// Built up with the `with_*` methods, e.g.
+/// `CompressionPolicy::new().with_min_size(256).with_skipped_type(MimeType::CSV)`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompressionPolicy {
+    min_size: u64,
+    max_size: u64,
+    skipped_types: Vec<MimeType>,
+}
+
+impl Default for CompressionPolicy {
+    fn default() -> Self {
+        Self { min_size: DEFAULT_MIN_SIZE, max_size: DEFAULT_MAX_SIZE, skipped_types: Vec::new() }
+    }
+}
+
+impl CompressionPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Leave bodies smaller than `min_size` bytes uncompressed
+    pub fn with_min_size(mut self, min_size: u64) -> Self {
+        self.min_size = min_size;
+        self
+    }
+
+    /// Leave bodies larger than `max_size` bytes uncompressed, they are held in memory while compressing
+    pub fn with_max_size(mut self, max_size: u64) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
+    /// Never compress this type, on top of the formats that are compressed already,
+    /// a subtype of `*` skips the whole top level type
+    pub fn with_skipped_type(mut self, mime_type: MimeType) -> Self {
+        self.skipped_types.push(mime_type);
+        self
+    }
+
+    /// Whether a body of this type and size gets compressed for clients that accept it
+    pub fn applies_to(&self, content_type: &MimeType, length: u64) -> bool {
+        let skipped = self.skipped_types.iter().any(|skipped| {
+            skipped.main_type() == content_type.main_type() && (skipped.subtype() == "*" || skipped.subtype() == content_type.subtype())
+        });
+        (self.min_size..=self.max_size).contains(&length) && !skipped && !is_already_compressed(content_type)
+    }
+
+    /// The best coding the client accepts, no header or one preferring `identity` gets none
+    pub fn negotiate(&self, accept_encoding: Option<&[QualityItem<String>]>) -> Option<ContentCoding> {
+        let accept_encoding = accept_encoding?;
+        let identity = encoding_quality(accept_encoding, "identity");
+        let mut best = None;
+        for coding in CODINGS {
+            let quality = encoding_quality(accept_encoding, coding.token());
+            if quality.is_acceptable() && quality >= identity && best.is_none_or(|(_, best_quality)| quality > best_quality) {
+                best = Some((coding, quality));
+            }
+        }
+        best.map(|(coding, _)| coding)
+    }
+}
+
+/// Formats that are compressed already, or opaque binary, so compressing them again only costs time
+pub fn is_already_compressed(mime_type: &MimeType) -> bool {
+    match (mime_type.main_type(), mime_type.subtype()) {
+        ("image", subtype) => !matches!(subtype, "svg+xml" | "bmp" | "tiff" | "x-icon" | "vnd.microsoft.icon"),
+        ("audio", subtype) => !matches!(subtype, "wav" | "midi"),
+        ("video", _) => true,
+        ("font", subtype) => matches!(subtype, "woff" | "woff2"),
+        ("application", subtype) => matches!(subtype,
+            "octet-stream" | "pdf" | "zip" | "gzip" | "x-gzip" | "zstd" | "x-bzip2" | "x-xz" | "x-brotli"
+            | "x-7z-compressed" | "vnd.rar" | "java-archive" | "vnd.android.package-archive" | "vnd.debian.binary-package"
+        ) || subtype.ends_with("+zip") || subtype.starts_with("vnd.openxmlformats-") || subtype.starts_with("vnd.oasis.opendocument."),
+        _ => false,
+    }
+}
+
+/// Compress with one of the codings the server can produce, brotli is only ever served precompressed
+pub fn compress(coding: ContentCoding, data: &[u8]) -> Option<Vec<u8>> {
+    match coding {
+        ContentCoding::Gzip => Some(gzip(data)),
+        ContentCoding::Deflate => Some(zlib(data)),
+        ContentCoding::Brotli => None,
+    }
+}
+
+/// Whether the response for this body depends on `Accept-Encoding` in the request being answered,
+/// in which case it has to say `Vary: Accept-Encoding` whether it ends up compressed or not
+pub fn varies_on_encoding(context: &ResponseContext, content_type: &MimeType, length: u64) -> bool {
+    context.compression.as_ref().is_some_and(|policy| policy.applies_to(content_type, length))
+}
+
+/// The coding to compress this body with for the request being answered, if any
+pub fn negotiated_coding(context: &ResponseContext, content_type: &MimeType, length: u64) -> Option<ContentCoding> {
+    context.compression.as_ref().filter(|policy| policy.applies_to(content_type, length))?;
+    context.content_coding
+}
+
+/// Adds `Accept-Encoding` to the `Vary` header, keeping whatever else it varies on
+pub fn vary_on_accept_encoding(headers: &mut HttpHeaders) {
+    let mut vary = headers.get_list("Vary");
+    if vary.iter().any(|name| name == "*" || name.eq_ignore_ascii_case("Accept-Encoding")) {
+        return;
+    }
+    vary.push("Accept-Encoding".to_string());
+    headers.set(HttpHeader::Custom("Vary".to_string(), vary.join(", ")));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http_builder::negotiation::parse_accept_encoding;
+
+    #[test]
+    fn test_negotiate() {
+        let policy = CompressionPolicy::new();
+        let negotiate = |accept_encoding: &str| policy.negotiate(parse_accept_encoding(accept_encoding).as_deref());
+
+        assert_eq!(negotiate("gzip, deflate, br"), Some(ContentCoding::Gzip));
+        assert_eq!(negotiate("deflate, gzip;q=0.5"), Some(ContentCoding::Deflate));
+        assert_eq!(negotiate("br"), None);
+        assert_eq!(negotiate("*"), Some(ContentCoding::Gzip));
+        assert_eq!(negotiate("gzip;q=0.5, identity"), None);
+        assert_eq!(negotiate("identity;q=0, deflate;q=0.1"), Some(ContentCoding::Deflate));
+        assert_eq!(policy.negotiate(None), None);
+    }
+
+    #[test]
+    fn test_applies_to() {
+        let policy = CompressionPolicy::new().with_skipped_type(MimeType::from_static("text", "*"));
+        assert!(policy.applies_to(&MimeType::JSON, 2048));
+        assert!(policy.applies_to(&MimeType::SVG, 2048));
+        assert!(!policy.applies_to(&MimeType::JSON, 100));
+        assert!(!policy.applies_to(&MimeType::JSON, DEFAULT_MAX_SIZE + 1));
+        assert!(!policy.applies_to(&MimeType::HTML, 2048));
+        assert!(!policy.applies_to(&MimeType::PNG, 2048));
+        assert!(!policy.applies_to(&"application/zip".parse().unwrap(), 2048));
+        assert!(!policy.applies_to(&"font/woff2".parse().unwrap(), 2048));
+    }
+
+    #[test]
+    fn test_vary_on_accept_encoding() {
+        let mut headers = HttpHeaders::new();
+        vary_on_accept_encoding(&mut headers);
+        assert_eq!(headers.get("Vary"), Some("Accept-Encoding".to_string()));
+
+        let mut headers = HttpHeaders::new();
+        headers.add(HttpHeader::Custom("Vary".to_string(), "Accept-Language".to_string()));
+        vary_on_accept_encoding(&mut headers);
+        vary_on_accept_encoding(&mut headers);
+        assert_eq!(headers.get_all("Vary"), vec!["Accept-Language, Accept-Encoding".to_string()]);
+    }
+}
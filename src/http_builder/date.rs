@@ -0,0 +1,308 @@
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const DAY_NAMES: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+const FULL_DAY_NAMES: [&str; 7] = ["Thursday", "Friday", "Saturday", "Sunday", "Monday", "Tuesday", "Wednesday"];
+const MONTH_NAMES: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+
+const SECONDS_PER_DAY: u64 = 86_400;
+
+/// A time broken down into its calendar fields, in UTC
+struct CivilTime {
+    year: u64,
+    month: u64,
+    day: u64,
+    hour: u64,
+    minute: u64,
+    second: u64,
+    /// index into `DAY_NAMES`
+    weekday: usize,
+}
+
+impl CivilTime {
+    /// times before the unix epoch are clamped to it
+    fn from_system_time(time: SystemTime) -> Self {
+        let seconds = time.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        let days = seconds / SECONDS_PER_DAY;
+        let seconds_of_day = seconds % SECONDS_PER_DAY;
+        let (year, month, day) = civil_from_days(days);
+        CivilTime {
+            year,
+            month,
+            day,
+            hour: seconds_of_day / 3600,
+            minute: seconds_of_day / 60 % 60,
+            second: seconds_of_day % 60,
+            weekday: (days % 7) as usize,
+        }
+    }
+
+    fn month_name(&self) -> &'static str {
+        MONTH_NAMES[(self.month - 1) as usize]
+    }
+}
+
+/// Format a time as an IMF-fixdate, e.g. `Sun, 06 Nov 1994 08:49:37 GMT`
+///
+/// this is the only format a server should send, times before the unix epoch are clamped to it
+pub fn format_http_date(time: SystemTime) -> String {
+    let t = CivilTime::from_system_time(time);
+    format!(
+        "{}, {:02} {} {:04} {:02}:{:02}:{:02} GMT",
+        DAY_NAMES[t.weekday], t.day, t.month_name(), t.year, t.hour, t.minute, t.second
+    )
+}
+
+/// Format a time in the obsolete RFC 850 format, e.g. `Sunday, 06-Nov-94 08:49:37 GMT`
+pub fn format_rfc850_date(time: SystemTime) -> String {
+    let t = CivilTime::from_system_time(time);
+    format!(
+        "{}, {:02}-{}-{:02} {:02}:{:02}:{:02} GMT",
+        FULL_DAY_NAMES[t.weekday], t.day, t.month_name(), t.year % 100, t.hour, t.minute, t.second
+    )
+}
+
+/// Format a time in the obsolete asctime format, e.g. `Sun Nov  6 08:49:37 1994`
+pub fn format_asctime_date(time: SystemTime) -> String {
+    let t = CivilTime::from_system_time(time);
+    format!(
+        "{} {} {:>2} {:02}:{:02}:{:02} {:04}",
+        DAY_NAMES[t.weekday], t.month_name(), t.day, t.hour, t.minute, t.second, t.year
+    )
+}
+
+/// The current time as an IMF-fixdate, for the `Date` header
+///
+/// formatted at most once a second, every response within that second shares the string
+pub fn http_date_now() -> String {
+    static CACHE: Mutex<Option<(u64, String)>> = Mutex::new(None);
+
+    let now = SystemTime::now();
+    let seconds = now.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let mut cache = CACHE.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    match &*cache {
+        Some((cached_seconds, date)) if *cached_seconds == seconds => date.clone(),
+        _ => {
+            let date = format_http_date(now);
+            *cache = Some((seconds, date.clone()));
+            date
+        }
+    }
+}
+
+/// Parse an HTTP-date in any of the three formats recipients have to accept,
+/// returning None if the value is none of them
+pub fn parse_http_date(value: &str) -> Option<SystemTime> {
+    let value = value.trim();
+    parse_imf_fixdate(value)
+        .or_else(|| parse_rfc850_date(value))
+        .or_else(|| parse_asctime_date(value))
+}
+
+/// Parse an IMF-fixdate, e.g. `Sun, 06 Nov 1994 08:49:37 GMT`
+pub fn parse_imf_fixdate(value: &str) -> Option<SystemTime> {
+    let (day_name, rest) = value.split_once(", ")?;
+    if !DAY_NAMES.contains(&day_name) {
+        return None;
+    }
+    let parts: Vec<&str> = rest.split(' ').collect();
+    if parts.len() != 5 || parts[4] != "GMT" || parts[0].len() != 2 || parts[2].len() != 4 {
+        return None;
+    }
+    let day = parse_number(parts[0])?;
+    let month = parse_month(parts[1])?;
+    let year = parse_number(parts[2])?;
+    let (hour, minute, second) = parse_time_of_day(parts[3])?;
+
+    to_system_time(year, month, day, hour, minute, second)
+}
+
+/// Parse an RFC 850 date, e.g. `Sunday, 06-Nov-94 08:49:37 GMT`
+///
+/// the two digit year is taken as the most recent year ending in those digits that isn't
+/// more than 50 years in the future
+pub fn parse_rfc850_date(value: &str) -> Option<SystemTime> {
+    let now = CivilTime::from_system_time(SystemTime::now());
+    parse_rfc850_date_in(value, now.year)
+}
+
+fn parse_rfc850_date_in(value: &str, current_year: u64) -> Option<SystemTime> {
+    let (day_name, rest) = value.split_once(", ")?;
+    if !FULL_DAY_NAMES.contains(&day_name) {
+        return None;
+    }
+    let parts: Vec<&str> = rest.split(' ').collect();
+    if parts.len() != 3 || parts[2] != "GMT" {
+        return None;
+    }
+    let date: Vec<&str> = parts[0].split('-').collect();
+    if date.len() != 3 || date[0].len() != 2 || date[2].len() != 2 {
+        return None;
+    }
+    let day = parse_number(date[0])?;
+    let month = parse_month(date[1])?;
+    let mut year = current_year / 100 * 100 + parse_number(date[2])?;
+    if year > current_year + 50 {
+        year -= 100;
+    }
+    let (hour, minute, second) = parse_time_of_day(parts[1])?;
+
+    to_system_time(year, month, day, hour, minute, second)
+}
+
+/// Parse an asctime date, e.g. `Sun Nov  6 08:49:37 1994`
+pub fn parse_asctime_date(value: &str) -> Option<SystemTime> {
+    // fixed width, the day of the month is padded with a space
+    if value.len() != 24 || !value.is_ascii() {
+        return None;
+    }
+    let separators_ok = [3, 7, 10, 19].iter().all(|index| value.as_bytes()[*index] == b' ');
+    if !separators_ok || !DAY_NAMES.contains(&&value[0..3]) {
+        return None;
+    }
+    let month = parse_month(&value[4..7])?;
+    let day = parse_number(value[8..10].trim_start_matches(' '))?;
+    let (hour, minute, second) = parse_time_of_day(&value[11..19])?;
+    let year = parse_number(&value[20..24])?;
+
+    to_system_time(year, month, day, hour, minute, second)
+}
+
+fn parse_month(value: &str) -> Option<u64> {
+    MONTH_NAMES.iter().position(|m| *m == value).map(|index| index as u64 + 1)
+}
+
+fn parse_number(value: &str) -> Option<u64> {
+    if value.is_empty() || !value.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    value.parse().ok()
+}
+
+fn parse_time_of_day(value: &str) -> Option<(u64, u64, u64)> {
+    let parts: Vec<&str> = value.split(':').collect();
+    if parts.len() != 3 || parts.iter().any(|part| part.len() != 2) {
+        return None;
+    }
+    Some((parse_number(parts[0])?, parse_number(parts[1])?, parse_number(parts[2])?))
+}
+
+fn to_system_time(year: u64, month: u64, day: u64, hour: u64, minute: u64, second: u64) -> Option<SystemTime> {
+    // leap seconds (60) are allowed by the grammar
+    if year < 1970 || day == 0 || day > days_in_month(year, month) || hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
+    let days = days_from_civil(year, month, day);
+    let seconds = days * SECONDS_PER_DAY + hour * 3600 + minute * 60 + second;
+    Some(UNIX_EPOCH + Duration::from_secs(seconds))
+}
+
+fn is_leap_year(year: u64) -> bool {
+    (year.is_multiple_of(4) && !year.is_multiple_of(100)) || year.is_multiple_of(400)
+}
+
+fn days_in_month(year: u64, month: u64) -> u64 {
+    match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        _ => 0,
+    }
+}
+
+/// Days since 1970-01-01 to (year, month, day), from Howard Hinnant's date algorithms
+fn civil_from_days(days: u64) -> (u64, u64, u64) {
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let day_of_era = z - era * 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// (year, month, day) to days since 1970-01-01, the inverse of `civil_from_days`
+fn days_from_civil(year: u64, month: u64, day: u64) -> u64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year / 400;
+    let year_of_era = year - era * 400;
+    let month_index = if month > 2 { month - 3 } else { month + 9 };
+    let day_of_year = (153 * month_index + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_http_date() {
+        assert_eq!(format_http_date(UNIX_EPOCH), "Thu, 01 Jan 1970 00:00:00 GMT");
+        assert_eq!(format_http_date(UNIX_EPOCH + Duration::from_secs(784111777)), "Sun, 06 Nov 1994 08:49:37 GMT");
+        assert_eq!(format_http_date(UNIX_EPOCH + Duration::from_secs(951782400)), "Tue, 29 Feb 2000 00:00:00 GMT");
+    }
+
+    #[test]
+    fn test_parse_http_date() {
+        assert_eq!(parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT"), Some(UNIX_EPOCH + Duration::from_secs(784111777)));
+        assert_eq!(parse_http_date("Tue, 29 Feb 2000 00:00:00 GMT"), Some(UNIX_EPOCH + Duration::from_secs(951782400)));
+
+        assert_eq!(parse_http_date("Sun, 06 Nov 1994 08:49:37 UTC"), None);
+        assert_eq!(parse_http_date("Sun, 6 Nov 1994 08:49:37 GMT"), None);
+        assert_eq!(parse_http_date("Mon, 29 Feb 1999 00:00:00 GMT"), None);
+        assert_eq!(parse_http_date("garbage"), None);
+    }
+
+    /// The three representations of the same instant given in RFC 9110 section 5.6.7
+    #[test]
+    fn test_rfc_examples() {
+        let expected = Some(UNIX_EPOCH + Duration::from_secs(784111777));
+        assert_eq!(parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT"), expected);
+        assert_eq!(parse_http_date("Sunday, 06-Nov-94 08:49:37 GMT"), expected);
+        assert_eq!(parse_http_date("Sun Nov  6 08:49:37 1994"), expected);
+
+        let time = UNIX_EPOCH + Duration::from_secs(784111777);
+        assert_eq!(format_rfc850_date(time), "Sunday, 06-Nov-94 08:49:37 GMT");
+        assert_eq!(format_asctime_date(time), "Sun Nov  6 08:49:37 1994");
+    }
+
+    #[test]
+    fn test_rfc850_century() {
+        let time = |seconds| Some(UNIX_EPOCH + Duration::from_secs(seconds));
+        //2026: "94" is 1994, "70" is 2070 (44 years ahead) and "77" is 1977 (51 years ahead otherwise)
+        assert_eq!(parse_rfc850_date_in("Sunday, 06-Nov-94 08:49:37 GMT", 2026), time(784111777));
+        assert_eq!(parse_rfc850_date_in("Wednesday, 01-Jan-70 00:00:00 GMT", 2026), time(3155760000));
+        assert_eq!(parse_rfc850_date_in("Saturday, 01-Jan-77 00:00:00 GMT", 2026), time(220924800));
+        assert_eq!(parse_rfc850_date_in("Tuesday, 29-Feb-00 00:00:00 GMT", 2026), time(951782400));
+
+        assert_eq!(parse_rfc850_date_in("Sun, 06-Nov-94 08:49:37 GMT", 2026), None);
+        assert_eq!(parse_rfc850_date_in("Sunday, 06-Nov-1994 08:49:37 GMT", 2026), None);
+        assert_eq!(parse_rfc850_date_in("Sunday, 06-Nov-94 08:49:37 UTC", 2026), None);
+    }
+
+    #[test]
+    fn test_asctime() {
+        assert_eq!(parse_asctime_date("Thu Jan  1 00:00:00 1970"), Some(UNIX_EPOCH));
+        assert_eq!(parse_asctime_date("Tue Feb 29 00:00:00 2000"), Some(UNIX_EPOCH + Duration::from_secs(951782400)));
+        assert_eq!(parse_asctime_date("Sun Nov 6 08:49:37 1994"), None);
+        assert_eq!(parse_asctime_date("Sun Nov  6 08:49:37 94  "), None);
+        assert_eq!(parse_asctime_date("Sun Nov 31 08:49:37 1994"), None);
+    }
+
+    #[test]
+    fn test_round_trips() {
+        //a spread of instants across leap years, month ends and the end of 32-bit time
+        for seconds in [0, 68169599, 68169600, 951868799, 1234567890, 2147483647, 4102444800, 253402300799] {
+            let time = UNIX_EPOCH + Duration::from_secs(seconds);
+            assert_eq!(parse_http_date(&format_http_date(time)), Some(time), "{}", seconds);
+            assert_eq!(parse_http_date(&format_asctime_date(time)), Some(time), "{}", seconds);
+        }
+        assert_eq!(format_http_date(UNIX_EPOCH + Duration::from_secs(2147483647)), "Tue, 19 Jan 2038 03:14:07 GMT");
+        assert_eq!(format_http_date(UNIX_EPOCH + Duration::from_secs(253402300799)), "Fri, 31 Dec 9999 23:59:59 GMT");
+    }
+}
@@ -0,0 +1,388 @@
+//! Parsing of the `Accept`, `Accept-Language`, `Accept-Charset` and `Accept-Encoding`
+//! request headers, and picking the best of the representations a server can offer
+//! (RFC 9110 section 12).
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::http_builder::HttpHeaders;
+use crate::server::util::mime_types::MimeType;
+
+/// A q-value, kept in thousandths since that is all the precision the grammar allows
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Quality(u16);
+
+impl Quality {
+    pub const MAX: Quality = Quality(1000);
+    pub const ZERO: Quality = Quality(0);
+
+    /// `None` for anything over 1000
+    pub fn from_thousandths(thousandths: u16) -> Option<Self> {
+        (thousandths <= 1000).then_some(Quality(thousandths))
+    }
+
+    pub fn thousandths(&self) -> u16 {
+        self.0
+    }
+
+    /// A quality of zero means "not acceptable"
+    pub fn is_acceptable(&self) -> bool {
+        self.0 > 0
+    }
+}
+
+impl Default for Quality {
+    fn default() -> Self {
+        Quality::MAX
+    }
+}
+
+impl FromStr for Quality {
+    type Err = ();
+
+    /// `qvalue = ( "0" [ "." 0*3DIGIT ] ) / ( "1" [ "." 0*3("0") ] )`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (whole, fraction) = s.split_once('.').unwrap_or((s, ""));
+        if fraction.len() > 3 || !fraction.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(());
+        }
+        let fraction = format!("{:0<3}", fraction).parse::<u16>().map_err(|_| ())?;
+        match whole {
+            "0" => Ok(Quality(fraction)),
+            "1" if fraction == 0 => Ok(Quality::MAX),
+            _ => Err(()),
+        }
+    }
+}
+
+impl fmt::Display for Quality {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            1000 => write!(f, "1"),
+            0 => write!(f, "0"),
+            thousandths => write!(f, "0.{}", format!("{:03}", thousandths).trim_end_matches('0')),
+        }
+    }
+}
+
+/// One element of an `Accept-*` list with the quality the client gave it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QualityItem<T> {
+    pub value: T,
+    pub quality: Quality,
+}
+
+impl<T> QualityItem<T> {
+    pub fn new(value: T, quality: Quality) -> Self {
+        Self { value, quality }
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for QualityItem<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.quality {
+            Quality::MAX => write!(f, "{}", self.value),
+            quality => write!(f, "{};q={}", self.value, quality),
+        }
+    }
+}
+
+/// Splits on `separator` wherever it isn't inside a quoted string
+fn split_unquoted(s: &str, separator: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut quoted = false;
+    let mut escaped = false;
+    for (index, c) in s.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            c if c == separator && !quoted => {
+                parts.push(&s[start..index]);
+                start = index + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    parts.push(&s[start..]);
+    parts
+}
+
+/// Parses a comma separated list of `value *( ; param )` elements, each with an optional `q` parameter
+///
+/// whatever comes before `q` goes to `parse_value`, parameters after it are extensions and ignored,
+/// the list is ordered best first, keeping the client's order for equal qualities
+///
+/// `None` if any element is malformed, RFC 9110 lets the server treat such a header as absent
+fn parse_quality_list<T>(value: &str, parse_value: impl Fn(&str) -> Option<T>) -> Option<Vec<QualityItem<T>>> {
+    let mut items = Vec::new();
+    for element in split_unquoted(value, ',') {
+        // empty elements are allowed by the list syntax, `gzip, , br`
+        if element.trim().is_empty() {
+            continue;
+        }
+        let parameters = split_unquoted(element, ';');
+        let q_index = parameters.iter()
+            .position(|parameter| parameter.split_once('=').is_some_and(|(name, _)| name.trim().eq_ignore_ascii_case("q")))
+            .unwrap_or(parameters.len());
+        let quality = match parameters.get(q_index) {
+            Some(parameter) => parameter.split_once('=')?.1.trim().parse().ok()?,
+            None => Quality::MAX,
+        };
+        let value = parse_value(parameters[..q_index].join(";").trim())?;
+        items.push(QualityItem::new(value, quality));
+    }
+    // stable, so equal qualities stay in the order they were sent
+    items.sort_by_key(|item| std::cmp::Reverse(item.quality));
+    Some(items)
+}
+
+fn parse_token(value: &str) -> Option<String> {
+    let is_token = !value.is_empty() && value.bytes().all(|b| b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b));
+    is_token.then(|| value.to_string())
+}
+
+/// `Accept`, media ranges such as `text/*;q=0.5` or `text/html;level=1`
+pub fn parse_accept(value: &str) -> Option<Vec<QualityItem<MimeType>>> {
+    parse_quality_list(value, |range| {
+        let range = MimeType::from_str(range).ok()?;
+        // `*/html` isn't a media range
+        (range.main_type() != "*" || range.subtype() == "*").then_some(range)
+    })
+}
+
+/// `Accept-Language`, language ranges such as `en-GB` or `*`
+pub fn parse_accept_language(value: &str) -> Option<Vec<QualityItem<String>>> {
+    parse_quality_list(value, |range| {
+        let valid = range == "*" || range.split('-').all(|part| {
+            (1..=8).contains(&part.len()) && part.bytes().all(|b| b.is_ascii_alphanumeric())
+        });
+        valid.then(|| range.to_string())
+    })
+}
+
+/// `Accept-Charset`, charsets such as `utf-8` or `*`
+pub fn parse_accept_charset(value: &str) -> Option<Vec<QualityItem<String>>> {
+    parse_quality_list(value, parse_token)
+}
+
+/// `Accept-Encoding`, content codings such as `gzip`, `identity` or `*`
+pub fn parse_accept_encoding(value: &str) -> Option<Vec<QualityItem<String>>> {
+    parse_quality_list(value, parse_token)
+}
+
+/// How well a media range matches a type, `None` if it doesn't
+///
+/// parameters on the range have to be present on the type, and make the match more specific
+fn media_range_specificity(range: &MimeType, mime_type: &MimeType) -> Option<usize> {
+    let parameters_match = range.parameters().iter()
+        .all(|(name, value)| mime_type.parameter(name).is_some_and(|offered| offered.eq_ignore_ascii_case(value)));
+    match (range.main_type(), range.subtype()) {
+        ("*", "*") => Some(0),
+        (main_type, "*") if main_type == mime_type.main_type() => Some(1),
+        (main_type, subtype) if main_type == mime_type.main_type() && subtype == mime_type.subtype() && parameters_match =>
+            Some(2 + range.parameters().len()),
+        _ => None,
+    }
+}
+
+/// The quality of a type, from the most specific range that matches it
+pub fn media_type_quality(accept: &[QualityItem<MimeType>], mime_type: &MimeType) -> Quality {
+    accept.iter()
+        .filter_map(|item| media_range_specificity(&item.value, mime_type).map(|specificity| (specificity, item.quality)))
+        // the first of the equally specific ranges, as the list is ordered best first
+        .rev()
+        .max_by_key(|(specificity, _)| *specificity)
+        .map_or(Quality::ZERO, |(_, quality)| quality)
+}
+
+/// The quality of a language tag, from the longest range that matches it (RFC 4647 basic filtering)
+pub fn language_quality(accept_language: &[QualityItem<String>], tag: &str) -> Quality {
+    let matches = |range: &str| range == "*" || tag.eq_ignore_ascii_case(range) || (
+        tag.len() > range.len() && tag.is_char_boundary(range.len())
+            && tag[..range.len()].eq_ignore_ascii_case(range) && tag[range.len()..].starts_with('-')
+    );
+    accept_language.iter()
+        .filter(|item| matches(&item.value))
+        .rev()
+        .max_by_key(|item| if item.value == "*" { 0 } else { item.value.len() })
+        .map_or(Quality::ZERO, |item| item.quality)
+}
+
+/// The quality of a charset, listed ones take precedence over `*`
+pub fn charset_quality(accept_charset: &[QualityItem<String>], charset: &str) -> Quality {
+    token_quality(accept_charset, charset).unwrap_or(Quality::ZERO)
+}
+
+/// The quality of a content coding, listed ones take precedence over `*`
+///
+/// `identity` is acceptable unless it is refused, by itself or by `*;q=0`
+pub fn encoding_quality(accept_encoding: &[QualityItem<String>], coding: &str) -> Quality {
+    let aliases = |name: &str| name.eq_ignore_ascii_case(coding)
+        || (coding.eq_ignore_ascii_case("gzip") && name.eq_ignore_ascii_case("x-gzip"))
+        || (coding.eq_ignore_ascii_case("compress") && name.eq_ignore_ascii_case("x-compress"));
+    let listed = accept_encoding.iter().find(|item| aliases(&item.value)).map(|item| item.quality);
+    match listed.or_else(|| token_quality(accept_encoding, "*")) {
+        Some(quality) => quality,
+        None if coding.eq_ignore_ascii_case("identity") => Quality::MAX,
+        None => Quality::ZERO,
+    }
+}
+
+fn token_quality(items: &[QualityItem<String>], token: &str) -> Option<Quality> {
+    let listed = items.iter().find(|item| item.value.eq_ignore_ascii_case(token));
+    listed.or_else(|| items.iter().find(|item| item.value == "*")).map(|item| item.quality)
+}
+
+/// The acceptable offer with the highest quality, ties go to the earlier offer
+fn best_offer<T>(offers: &[T], quality: impl Fn(&T) -> Quality) -> Option<&T> {
+    let mut best: Option<(&T, Quality)> = None;
+    for offer in offers {
+        let quality = quality(offer);
+        if quality.is_acceptable() && best.is_none_or(|(_, best_quality)| quality > best_quality) {
+            best = Some((offer, quality));
+        }
+    }
+    best.map(|(offer, _)| offer)
+}
+
+/// What a request will accept, from its `Accept-*` headers
+///
+/// a header that wasn't sent, or couldn't be parsed, accepts anything, so the first offer wins
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Preferences {
+    pub accept: Option<Vec<QualityItem<MimeType>>>,
+    pub accept_language: Option<Vec<QualityItem<String>>>,
+    pub accept_charset: Option<Vec<QualityItem<String>>>,
+    pub accept_encoding: Option<Vec<QualityItem<String>>>,
+}
+
+impl Preferences {
+    pub fn from_headers(headers: &HttpHeaders) -> Self {
+        Self {
+            accept: headers.accept(),
+            accept_language: headers.accept_language(),
+            accept_charset: headers.accept_charset(),
+            accept_encoding: headers.accept_encoding(),
+        }
+    }
+
+    /// The best of the types the server can send, `None` means 406 Not Acceptable
+    pub fn media_type<'a>(&self, offers: &'a [MimeType]) -> Option<&'a MimeType> {
+        match &self.accept {
+            Some(accept) => best_offer(offers, |offer| media_type_quality(accept, offer)),
+            None => offers.first(),
+        }
+    }
+
+    pub fn language<'a>(&self, offers: &[&'a str]) -> Option<&'a str> {
+        match &self.accept_language {
+            Some(accept_language) => best_offer(offers, |offer| language_quality(accept_language, offer)).copied(),
+            None => offers.first().copied(),
+        }
+    }
+
+    pub fn charset<'a>(&self, offers: &[&'a str]) -> Option<&'a str> {
+        match &self.accept_charset {
+            Some(accept_charset) => best_offer(offers, |offer| charset_quality(accept_charset, offer)).copied(),
+            None => offers.first().copied(),
+        }
+    }
+
+    pub fn encoding<'a>(&self, offers: &[&'a str]) -> Option<&'a str> {
+        match &self.accept_encoding {
+            Some(accept_encoding) => best_offer(offers, |offer| encoding_quality(accept_encoding, offer)).copied(),
+            None => offers.first().copied(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quality(s: &str) -> Quality {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_quality() {
+        assert_eq!(quality("1"), Quality::MAX);
+        assert_eq!(quality("1.000"), Quality::MAX);
+        assert_eq!(quality("0"), Quality::ZERO);
+        assert_eq!(quality("0.5").thousandths(), 500);
+        assert_eq!(quality("0.005").thousandths(), 5);
+        assert!("1.5".parse::<Quality>().is_err());
+        assert!("0.1234".parse::<Quality>().is_err());
+        assert!("2".parse::<Quality>().is_err());
+        assert!(".5".parse::<Quality>().is_err());
+        assert_eq!(quality("0.50").to_string(), "0.5");
+        assert_eq!(quality("0.005").to_string(), "0.005");
+    }
+
+    #[test]
+    fn test_parse_accept() {
+        let accept = parse_accept("text/*;q=0.3, text/html;q=0.7, text/html;level=1, text/html;level=2;q=0.4, */*;q=0.5").unwrap();
+        let values: Vec<String> = accept.iter().map(|item| item.to_string()).collect();
+        assert_eq!(values, vec!["text/html; level=1", "text/html;q=0.7", "*/*;q=0.5", "text/html; level=2;q=0.4", "text/*;q=0.3"]);
+
+        // the RFC 9110 example, section 12.5.1
+        let quality_of = |s: &str| media_type_quality(&accept, &s.parse().unwrap()).thousandths();
+        assert_eq!(quality_of("text/html;level=1"), 1000);
+        assert_eq!(quality_of("text/html"), 700);
+        assert_eq!(quality_of("text/plain"), 300);
+        assert_eq!(quality_of("image/jpeg"), 500);
+        assert_eq!(quality_of("text/html;level=2"), 400);
+        assert_eq!(quality_of("text/html;level=3"), 700);
+
+        assert!(parse_accept("text/html;q=2").is_none());
+        assert!(parse_accept("*/html").is_none());
+        assert_eq!(parse_accept("text/html;q=0.5;ext=\"a,b\"").unwrap(), vec![QualityItem::new(MimeType::HTML, quality("0.5"))]);
+        assert_eq!(parse_accept("").unwrap(), vec![]);
+    }
+
+    #[test]
+    fn test_negotiate_media_type() {
+        let offers = [MimeType::HTML, MimeType::JSON, MimeType::PLAIN_TEXT];
+        let preferred = |accept: &str| Preferences { accept: parse_accept(accept), ..Preferences::default() }.media_type(&offers).cloned();
+
+        assert_eq!(preferred("application/json"), Some(MimeType::JSON));
+        assert_eq!(preferred("text/*;q=0.5, application/json;q=0.4"), Some(MimeType::HTML));
+        assert_eq!(preferred("text/*, text/html;q=0"), Some(MimeType::PLAIN_TEXT));
+        assert_eq!(preferred("image/png"), None);
+        assert_eq!(Preferences::default().media_type(&offers), Some(&MimeType::HTML));
+    }
+
+    #[test]
+    fn test_negotiate_language() {
+        let preferences = Preferences { accept_language: parse_accept_language("fr-CH, fr;q=0.9, en;q=0.8, *;q=0.5"), ..Preferences::default() };
+        assert_eq!(preferences.language(&["en-US", "fr"]), Some("fr"));
+        assert_eq!(preferences.language(&["en-US", "de"]), Some("en-US"));
+        assert_eq!(preferences.language(&["de", "es"]), Some("de"));
+        assert_eq!(preferences.language(&["fr-CH", "fr"]), Some("fr-CH"));
+
+        let accept_language = parse_accept_language("en-GB, *;q=0").unwrap();
+        assert_eq!(language_quality(&accept_language, "en"), Quality::ZERO);
+        assert_eq!(language_quality(&accept_language, "en-gb-oed"), Quality::MAX);
+        assert!(parse_accept_language("en_GB").is_none());
+    }
+
+    #[test]
+    fn test_negotiate_charset_and_encoding() {
+        let preferences = Preferences {
+            accept_charset: parse_accept_charset("iso-8859-5, unicode-1-1;q=0.8"),
+            accept_encoding: parse_accept_encoding("gzip;q=0.5, br"),
+            ..Preferences::default()
+        };
+        assert_eq!(preferences.charset(&["utf-8", "ISO-8859-5"]), Some("ISO-8859-5"));
+        assert_eq!(preferences.charset(&["utf-8"]), None);
+        assert_eq!(preferences.encoding(&["gzip", "br"]), Some("br"));
+        assert_eq!(preferences.encoding(&["deflate", "identity"]), Some("identity"));
+
+        let accept_encoding = parse_accept_encoding("x-gzip, *;q=0").unwrap();
+        assert_eq!(encoding_quality(&accept_encoding, "gzip"), Quality::MAX);
+        assert_eq!(encoding_quality(&accept_encoding, "identity"), Quality::ZERO);
+        assert_eq!(encoding_quality(&parse_accept_encoding("").unwrap(), "identity"), Quality::MAX);
+    }
+}
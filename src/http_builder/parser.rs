@@ -0,0 +1,634 @@
+use std::fmt;
+use std::str::FromStr;
+
+use super::{HttpHeader, HttpHeaders, HttpMethod, HttpRequest, HttpRequestLine, HttpStatus, HttpVersion};
+
+/// Limits enforced while parsing, anything over them is rejected rather than buffered
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParserLimits {
+    /// Longest start line, header line or chunk-size line, in bytes
+    pub max_line_length: usize,
+    /// Most header fields (and separately, trailer fields) in one message
+    pub max_headers: usize,
+    /// Largest total size of the header section, in bytes
+    pub max_header_size: usize,
+    /// Largest body, after removing the chunked framing
+    pub max_body_size: u64,
+}
+
+impl Default for ParserLimits {
+    fn default() -> Self {
+        ParserLimits {
+            max_line_length: 8 * 1024,
+            max_headers: 100,
+            max_header_size: 64 * 1024,
+            max_body_size: 8 * 1024 * 1024,
+        }
+    }
+}
+
+/// Why a message couldn't be parsed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    /// The start line was longer than `max_line_length`
+    StartLineTooLong,
+    /// A header or chunk-size line was longer than `max_line_length`
+    LineTooLong,
+    TooManyHeaders,
+    HeadersTooLarge,
+    BodyTooLarge,
+    InvalidStartLine(String),
+    /// The method isn't one the server knows
+    UnknownMethod(String),
+    InvalidVersion(String),
+    /// A well formed version the server doesn't speak, e.g. HTTP/2.0
+    UnsupportedVersion(String),
+    InvalidHeader(String),
+    InvalidContentLength,
+    /// Both Transfer-Encoding and Content-Length, or a Transfer-Encoding that doesn't end in chunked
+    InvalidFraming,
+    InvalidChunk,
+    /// The input ended in the middle of a message
+    UnexpectedEof,
+}
+
+impl ParseError {
+    /// The status a server should answer with when a request fails to parse like this
+    pub fn status(&self) -> HttpStatus {
+        match self {
+            ParseError::StartLineTooLong => HttpStatus::UriTooLong,
+            ParseError::LineTooLong | ParseError::TooManyHeaders | ParseError::HeadersTooLarge => HttpStatus::RequestHeaderFieldsTooLarge,
+            ParseError::BodyTooLarge => HttpStatus::ContentTooLarge,
+            ParseError::UnknownMethod(_) => HttpStatus::NotImplemented,
+            ParseError::UnsupportedVersion(_) => HttpStatus::HttpVersionNotSupported,
+            _ => HttpStatus::BadRequest,
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::StartLineTooLong => write!(f, "Start line too long"),
+            ParseError::LineTooLong => write!(f, "Header line too long"),
+            ParseError::TooManyHeaders => write!(f, "Too many headers"),
+            ParseError::HeadersTooLarge => write!(f, "Header section too large"),
+            ParseError::BodyTooLarge => write!(f, "Body too large"),
+            ParseError::InvalidStartLine(line) => write!(f, "Invalid start line: {}", line),
+            ParseError::UnknownMethod(method) => write!(f, "Unknown method: {}", method),
+            ParseError::InvalidVersion(version) => write!(f, "Invalid HTTP version: {}", version),
+            ParseError::UnsupportedVersion(version) => write!(f, "Unsupported HTTP version: {}", version),
+            ParseError::InvalidHeader(line) => write!(f, "Invalid header: {}", line),
+            ParseError::InvalidContentLength => write!(f, "Invalid Content-Length"),
+            ParseError::InvalidFraming => write!(f, "Invalid message framing"),
+            ParseError::InvalidChunk => write!(f, "Invalid chunked encoding"),
+            ParseError::UnexpectedEof => write!(f, "Unexpected end of message"),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// The first line of a message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StartLine {
+    Request { method: HttpMethod, target: String, version: HttpVersion },
+    Response { version: HttpVersion, status: HttpStatus },
+}
+
+/// How the length of the body is determined
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BodyFraming {
+    None,
+    ContentLength(u64),
+    Chunked,
+    /// Response bodies without a length run until the connection closes
+    CloseDelimited,
+}
+
+/// Start line and headers of a message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MessageHead {
+    pub start_line: StartLine,
+    pub headers: HttpHeaders,
+    pub framing: BodyFraming,
+}
+
+/// A fully parsed message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpMessage {
+    pub head: MessageHead,
+    pub body: Vec<u8>,
+    /// Fields from the trailer section of a chunked body
+    pub trailers: HttpHeaders,
+}
+
+impl MessageHead {
+    /// The request this head starts, without its body, None if it is a response
+    pub fn to_request(&self) -> Option<HttpRequest> {
+        let StartLine::Request { method, target, version } = &self.start_line else {
+            return None;
+        };
+        let mut request = HttpRequest::new(HttpRequestLine::new(method.clone(), target).with_version(*version));
+        for header in self.headers.iter() {
+            request.add_header(header.clone());
+        }
+        Some(request)
+    }
+}
+
+impl HttpMessage {
+    /// The request this message describes, None if it is a response
+    pub fn into_request(self) -> Option<HttpRequest> {
+        let mut request = self.head.to_request()?;
+        request.set_body(self.body);
+        Some(request)
+    }
+}
+
+/// How far the parser has got
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseProgress {
+    /// The head isn't complete yet
+    NeedMore,
+    /// The head is available through `head()`, the body is still incomplete
+    HeadComplete,
+    /// The whole message is available through `into_message()`
+    Complete,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    StartLine,
+    Headers,
+    FixedBody(u64),
+    ChunkSize,
+    ChunkData(u64),
+    ChunkDataEnd,
+    Trailers,
+    CloseDelimited,
+    Done,
+}
+
+/// Incremental HTTP/1.x parser, bytes are fed in as they arrive from the connection
+///
+/// `feed` can be called with any split of the input, down to a byte at a time
+#[derive(Debug)]
+pub struct MessageParser {
+    is_request: bool,
+    limits: ParserLimits,
+    state: State,
+    buffer: Vec<u8>,
+    start_line: Option<StartLine>,
+    headers: HttpHeaders,
+    header_bytes: usize,
+    framing: BodyFraming,
+    body: Vec<u8>,
+    trailers: HttpHeaders,
+}
+
+impl MessageParser {
+    /// A parser for a request sent by a client
+    pub fn request(limits: ParserLimits) -> Self {
+        Self::new(true, limits)
+    }
+
+    /// A parser for a response sent by a server
+    pub fn response(limits: ParserLimits) -> Self {
+        Self::new(false, limits)
+    }
+
+    fn new(is_request: bool, limits: ParserLimits) -> Self {
+        MessageParser {
+            is_request,
+            limits,
+            state: State::StartLine,
+            buffer: Vec::new(),
+            start_line: None,
+            headers: HttpHeaders::new(),
+            header_bytes: 0,
+            framing: BodyFraming::None,
+            body: Vec::new(),
+            trailers: HttpHeaders::new(),
+        }
+    }
+
+    /// Feed more bytes in, parsing as far as they allow
+    pub fn feed(&mut self, data: &[u8]) -> Result<ParseProgress, ParseError> {
+        self.buffer.extend_from_slice(data);
+        let mut consumed = 0;
+        let result = self.advance(&mut consumed);
+        self.buffer.drain(..consumed);
+        result?;
+        Ok(self.progress())
+    }
+
+    /// Tell the parser the input has ended, which completes a close-delimited body
+    pub fn finish(&mut self) -> Result<ParseProgress, ParseError> {
+        match self.state {
+            State::CloseDelimited => {
+                self.state = State::Done;
+                Ok(ParseProgress::Complete)
+            }
+            State::Done => Ok(ParseProgress::Complete),
+            _ => Err(ParseError::UnexpectedEof),
+        }
+    }
+
+    pub fn progress(&self) -> ParseProgress {
+        match self.state {
+            State::StartLine | State::Headers => ParseProgress::NeedMore,
+            State::Done => ParseProgress::Complete,
+            _ => ParseProgress::HeadComplete,
+        }
+    }
+
+    /// The start line and headers, once they have been parsed
+    pub fn head(&self) -> Option<MessageHead> {
+        if self.progress() == ParseProgress::NeedMore {
+            return None;
+        }
+        Some(MessageHead {
+            start_line: self.start_line.clone()?,
+            headers: self.headers.clone(),
+            framing: self.framing,
+        })
+    }
+
+    /// Bytes fed in past the end of the message, e.g. a pipelined request
+    pub fn remaining(&self) -> &[u8] {
+        if self.state == State::Done { &self.buffer } else { &[] }
+    }
+
+    /// The parsed message, once it is complete
+    pub fn into_message(self) -> Option<HttpMessage> {
+        if self.state != State::Done {
+            return None;
+        }
+        Some(HttpMessage {
+            head: MessageHead {
+                start_line: self.start_line?,
+                headers: self.headers,
+                framing: self.framing,
+            },
+            body: self.body,
+            trailers: self.trailers,
+        })
+    }
+
+    fn advance(&mut self, consumed: &mut usize) -> Result<(), ParseError> {
+        loop {
+            match self.state {
+                State::StartLine => {
+                    let Some(line) = self.take_line(consumed, ParseError::StartLineTooLong)? else { return Ok(()) };
+                    // a server should ignore empty lines received before the request line
+                    if line.is_empty() {
+                        continue;
+                    }
+                    self.start_line = Some(self.parse_start_line(&line)?);
+                    self.state = State::Headers;
+                }
+                State::Headers => {
+                    let Some(line) = self.take_line(consumed, ParseError::LineTooLong)? else { return Ok(()) };
+                    if line.is_empty() {
+                        self.framing = self.body_framing()?;
+                        self.state = match self.framing {
+                            BodyFraming::None | BodyFraming::ContentLength(0) => State::Done,
+                            BodyFraming::ContentLength(length) => State::FixedBody(length),
+                            BodyFraming::Chunked => State::ChunkSize,
+                            BodyFraming::CloseDelimited => State::CloseDelimited,
+                        };
+                        continue;
+                    }
+                    let header = self.parse_field(&line)?;
+                    if self.headers.len() >= self.limits.max_headers {
+                        return Err(ParseError::TooManyHeaders);
+                    }
+                    self.headers.add(header);
+                }
+                State::FixedBody(remaining) => {
+                    let taken = self.take_body(consumed, remaining)?;
+                    if taken == 0 {
+                        return Ok(());
+                    }
+                    self.state = if taken == remaining { State::Done } else { State::FixedBody(remaining - taken) };
+                }
+                State::ChunkSize => {
+                    let Some(line) = self.take_line(consumed, ParseError::LineTooLong)? else { return Ok(()) };
+                    let size = parse_chunk_size(&line)?;
+                    self.state = if size == 0 { State::Trailers } else { State::ChunkData(size) };
+                }
+                State::ChunkData(remaining) => {
+                    let taken = self.take_body(consumed, remaining)?;
+                    if taken == 0 {
+                        return Ok(());
+                    }
+                    self.state = if taken == remaining { State::ChunkDataEnd } else { State::ChunkData(remaining - taken) };
+                }
+                State::ChunkDataEnd => {
+                    let Some(line) = self.take_line(consumed, ParseError::InvalidChunk)? else { return Ok(()) };
+                    if !line.is_empty() {
+                        return Err(ParseError::InvalidChunk);
+                    }
+                    self.state = State::ChunkSize;
+                }
+                State::Trailers => {
+                    let Some(line) = self.take_line(consumed, ParseError::LineTooLong)? else { return Ok(()) };
+                    if line.is_empty() {
+                        self.state = State::Done;
+                        continue;
+                    }
+                    let trailer = self.parse_field(&line)?;
+                    if self.trailers.len() >= self.limits.max_headers {
+                        return Err(ParseError::TooManyHeaders);
+                    }
+                    self.trailers.add(trailer);
+                }
+                State::CloseDelimited => {
+                    let available = (self.buffer.len() - *consumed) as u64;
+                    if available > 0 {
+                        self.take_body(consumed, available)?;
+                    }
+                    return Ok(());
+                }
+                State::Done => return Ok(()),
+            }
+        }
+    }
+
+    /// Take the next line, without its CRLF (a bare LF is accepted too)
+    fn take_line(&mut self, consumed: &mut usize, too_long: ParseError) -> Result<Option<String>, ParseError> {
+        let unread = &self.buffer[*consumed..];
+        let Some(end) = unread.iter().position(|b| *b == b'\n') else {
+            if unread.len() > self.limits.max_line_length {
+                return Err(too_long);
+            }
+            return Ok(None);
+        };
+        if end > self.limits.max_line_length {
+            return Err(too_long);
+        }
+        let line = &unread[..end];
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        // obsolete text in header values is kept as best we can rather than rejected
+        let line = String::from_utf8_lossy(line).into_owned();
+        *consumed += end + 1;
+        Ok(Some(line))
+    }
+
+    /// Move up to `wanted` bytes into the body, returning how many were moved
+    fn take_body(&mut self, consumed: &mut usize, wanted: u64) -> Result<u64, ParseError> {
+        let available = (self.buffer.len() - *consumed) as u64;
+        let taken = available.min(wanted);
+        if self.body.len() as u64 + taken > self.limits.max_body_size {
+            return Err(ParseError::BodyTooLarge);
+        }
+        self.body.extend_from_slice(&self.buffer[*consumed..*consumed + taken as usize]);
+        *consumed += taken as usize;
+        Ok(taken)
+    }
+
+    fn parse_start_line(&self, line: &str) -> Result<StartLine, ParseError> {
+        let invalid = || ParseError::InvalidStartLine(line.to_string());
+        if self.is_request {
+            let parts: Vec<&str> = line.split(' ').collect();
+            if parts.len() != 3 || parts.iter().any(|part| part.is_empty()) {
+                return Err(invalid());
+            }
+            let method = HttpMethod::from_str(parts[0]).map_err(|_| ParseError::UnknownMethod(parts[0].to_string()))?;
+            Ok(StartLine::Request {
+                method,
+                target: parts[1].to_string(),
+                version: parse_version(parts[2])?,
+            })
+        } else {
+            let (version, status) = line.split_once(' ').ok_or_else(invalid)?;
+            let status = HttpStatus::from_str(status).map_err(|_| invalid())?;
+            Ok(StartLine::Response { version: parse_version(version)?, status })
+        }
+    }
+
+    fn parse_field(&mut self, line: &str) -> Result<HttpHeader, ParseError> {
+        self.header_bytes += line.len() + 2;
+        if self.header_bytes > self.limits.max_header_size {
+            return Err(ParseError::HeadersTooLarge);
+        }
+        // obsolete line folding
+        if line.starts_with([' ', '\t']) {
+            return Err(ParseError::InvalidHeader(line.to_string()));
+        }
+        let (name, _) = line.split_once(':').ok_or_else(|| ParseError::InvalidHeader(line.to_string()))?;
+        // no whitespace is allowed between the field name and the colon
+        if name.is_empty() || !name.bytes().all(is_token_byte) {
+            return Err(ParseError::InvalidHeader(line.to_string()));
+        }
+        HttpHeader::from_str(line).map_err(|_| {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                ParseError::InvalidContentLength
+            } else {
+                ParseError::InvalidHeader(line.to_string())
+            }
+        })
+    }
+
+    /// Work out the body framing from the headers, following RFC 9112 section 6.3
+    fn body_framing(&self) -> Result<BodyFraming, ParseError> {
+        if let Some(StartLine::Response { status, .. }) = &self.start_line
+            && (status.is_informational() || *status == HttpStatus::NoContent || *status == HttpStatus::NotModified) {
+            return Ok(BodyFraming::None);
+        }
+
+        let transfer_codings = self.headers.get_list("Transfer-Encoding");
+        let content_lengths = self.headers.get_list("Content-Length");
+
+        if !transfer_codings.is_empty() {
+            if self.is_request && !content_lengths.is_empty() {
+                return Err(ParseError::InvalidFraming);
+            }
+            let chunked_last = transfer_codings.last().is_some_and(|coding| coding.eq_ignore_ascii_case("chunked"));
+            return match (chunked_last, self.is_request) {
+                (true, _) => Ok(BodyFraming::Chunked),
+                (false, true) => Err(ParseError::InvalidFraming),
+                (false, false) => Ok(BodyFraming::CloseDelimited),
+            };
+        }
+
+        if let Some(first) = content_lengths.first() {
+            // repeated Content-Length values must all agree
+            if content_lengths.iter().any(|length| length != first) {
+                return Err(ParseError::InvalidContentLength);
+            }
+            let length = first.parse::<u64>().map_err(|_| ParseError::InvalidContentLength)?;
+            if length > self.limits.max_body_size {
+                return Err(ParseError::BodyTooLarge);
+            }
+            return Ok(BodyFraming::ContentLength(length));
+        }
+
+        Ok(if self.is_request { BodyFraming::None } else { BodyFraming::CloseDelimited })
+    }
+}
+
+fn parse_version(version: &str) -> Result<HttpVersion, ParseError> {
+    let bytes = version.as_bytes();
+    let valid = bytes.len() == 8
+        && version.starts_with("HTTP/")
+        && bytes[5].is_ascii_digit()
+        && bytes[6] == b'.'
+        && bytes[7].is_ascii_digit();
+    if !valid {
+        return Err(ParseError::InvalidVersion(version.to_string()));
+    }
+    match (bytes[5], bytes[7]) {
+        (b'1', b'0') => Ok(HttpVersion::Http10),
+        // a later 1.x minor version is backwards compatible, so it is treated as 1.1
+        (b'1', _) => Ok(HttpVersion::Http11),
+        _ => Err(ParseError::UnsupportedVersion(version.to_string())),
+    }
+}
+
+fn parse_chunk_size(line: &str) -> Result<u64, ParseError> {
+    // chunk extensions after ';' are ignored
+    let size = line.split(';').next().unwrap_or("").trim();
+    if size.is_empty() || size.len() > 16 || !size.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(ParseError::InvalidChunk);
+    }
+    u64::from_str_radix(size, 16).map_err(|_| ParseError::InvalidChunk)
+}
+
+/// tchar from RFC 9110, the characters allowed in a field name
+fn is_token_byte(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&byte)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_request(input: &[u8]) -> Result<HttpMessage, ParseError> {
+        let mut parser = MessageParser::request(ParserLimits::default());
+        match parser.feed(input)? {
+            ParseProgress::Complete => Ok(parser.into_message().unwrap()),
+            _ => Err(ParseError::UnexpectedEof),
+        }
+    }
+
+    #[test]
+    fn test_simple_request() {
+        let message = parse_request(b"GET /index.html HTTP/1.1\r\nHost: example.com\r\nAccept: text/html\r\n\r\n").unwrap();
+        assert_eq!(message.head.start_line, StartLine::Request {
+            method: HttpMethod::GET,
+            target: "/index.html".to_string(),
+            version: HttpVersion::Http11,
+        });
+        assert_eq!(message.head.headers.host(), Some("example.com".to_string()));
+        assert_eq!(message.head.framing, BodyFraming::None);
+        assert!(message.body.is_empty());
+
+        let request = message.into_request().unwrap();
+        assert_eq!(request.path(), "/index.html");
+    }
+
+    #[test]
+    fn test_versions() {
+        let request = parse_request(b"GET / HTTP/1.0\r\n\r\n").unwrap().into_request().unwrap();
+        assert_eq!(request.version(), HttpVersion::Http10);
+        let request = parse_request(b"GET / HTTP/1.2\r\n\r\n").unwrap().into_request().unwrap();
+        assert_eq!(request.version(), HttpVersion::Http11);
+        assert_eq!(ParseError::UnsupportedVersion("HTTP/2.0".to_string()).status(), HttpStatus::HttpVersionNotSupported);
+    }
+
+    #[test]
+    fn test_incremental_feeding() {
+        let input = b"POST /upload HTTP/1.1\r\nContent-Length: 11\r\n\r\nhello world";
+        let mut parser = MessageParser::request(ParserLimits::default());
+        let mut progress = Vec::new();
+        for byte in input.iter() {
+            progress.push(parser.feed(&[*byte]).unwrap());
+        }
+        assert_eq!(progress[0], ParseProgress::NeedMore);
+        assert_eq!(progress[input.len() - 12], ParseProgress::HeadComplete);
+        assert_eq!(progress[input.len() - 1], ParseProgress::Complete);
+        assert_eq!(parser.into_message().unwrap().body, b"hello world");
+    }
+
+    #[test]
+    fn test_chunked_body_with_trailers() {
+        let message = parse_request(
+            b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n5;ext=1\r\nhello\r\n6\r\n world\r\n0\r\nX-Checksum: abc\r\n\r\n"
+        ).unwrap();
+        assert_eq!(message.head.framing, BodyFraming::Chunked);
+        assert_eq!(message.body, b"hello world");
+        assert_eq!(message.trailers.get("x-checksum"), Some("abc".to_string()));
+    }
+
+    #[test]
+    fn test_pipelined_remaining() {
+        let mut parser = MessageParser::request(ParserLimits::default());
+        let progress = parser.feed(b"GET /a HTTP/1.1\r\n\r\nGET /b HTTP/1.1\r\n\r\n").unwrap();
+        assert_eq!(progress, ParseProgress::Complete);
+        assert_eq!(parser.remaining(), b"GET /b HTTP/1.1\r\n\r\n");
+    }
+
+    #[test]
+    fn test_response_parsing() {
+        let mut parser = MessageParser::response(ParserLimits::default());
+        assert_eq!(parser.feed(b"HTTP/1.1 404 Not Found\r\nContent-Length: 3\r\n\r\nabc").unwrap(), ParseProgress::Complete);
+        let message = parser.into_message().unwrap();
+        assert_eq!(message.head.start_line, StartLine::Response { version: HttpVersion::Http11, status: HttpStatus::NotFound });
+
+        //without a length the body runs until the input ends
+        let mut parser = MessageParser::response(ParserLimits::default());
+        assert_eq!(parser.feed(b"HTTP/1.0 200 OK\r\n\r\nsome").unwrap(), ParseProgress::HeadComplete);
+        assert_eq!(parser.feed(b" data").unwrap(), ParseProgress::HeadComplete);
+        assert_eq!(parser.finish().unwrap(), ParseProgress::Complete);
+        assert_eq!(parser.into_message().unwrap().body, b"some data");
+
+        let mut parser = MessageParser::response(ParserLimits::default());
+        assert_eq!(parser.feed(b"HTTP/1.1 304 Not Modified\r\nContent-Length: 10\r\n\r\n").unwrap(), ParseProgress::Complete);
+    }
+
+    #[test]
+    fn test_malformed_requests() {
+        assert_eq!(parse_request(b"GET /\r\n\r\n"), Err(ParseError::InvalidStartLine("GET /".to_string())));
+        assert_eq!(parse_request(b"BREW / HTTP/1.1\r\n\r\n"), Err(ParseError::UnknownMethod("BREW".to_string())));
+        assert_eq!(parse_request(b"GET / HTTP/one\r\n\r\n"), Err(ParseError::InvalidVersion("HTTP/one".to_string())));
+        assert_eq!(parse_request(b"GET / HTTP/2.0\r\n\r\n"), Err(ParseError::UnsupportedVersion("HTTP/2.0".to_string())));
+        assert_eq!(parse_request(b"GET / HTTP/0.9\r\n\r\n"), Err(ParseError::UnsupportedVersion("HTTP/0.9".to_string())));
+        assert_eq!(parse_request(b"GET / HTTP/1.1\r\nHost : x\r\n\r\n"), Err(ParseError::InvalidHeader("Host : x".to_string())));
+        assert_eq!(parse_request(b"GET / HTTP/1.1\r\nNoColon\r\n\r\n"), Err(ParseError::InvalidHeader("NoColon".to_string())));
+        assert_eq!(parse_request(b"GET / HTTP/1.1\r\nX-A: 1\r\n folded\r\n\r\n"), Err(ParseError::InvalidHeader(" folded".to_string())));
+        assert_eq!(parse_request(b"POST / HTTP/1.1\r\nContent-Length: abc\r\n\r\n"), Err(ParseError::InvalidContentLength));
+        assert_eq!(parse_request(b"POST / HTTP/1.1\r\nContent-Length: +5\r\n\r\nhello"), Err(ParseError::InvalidContentLength));
+        assert_eq!(parse_request(b"POST / HTTP/1.1\r\nContent-Length: 1\r\nContent-Length: 2\r\n\r\n"), Err(ParseError::InvalidContentLength));
+        assert_eq!(parse_request(b"POST / HTTP/1.1\r\nContent-Length: 1\r\nTransfer-Encoding: chunked\r\n\r\n"), Err(ParseError::InvalidFraming));
+        assert_eq!(parse_request(b"POST / HTTP/1.1\r\nTransfer-Encoding: gzip\r\n\r\n"), Err(ParseError::InvalidFraming));
+        assert_eq!(parse_request(b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\nzz\r\n"), Err(ParseError::InvalidChunk));
+        assert_eq!(parse_request(b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n2\r\nabc\r\n"), Err(ParseError::InvalidChunk));
+    }
+
+    #[test]
+    fn test_limits() {
+        let limits = ParserLimits { max_line_length: 32, max_headers: 2, max_header_size: 64, max_body_size: 4 };
+
+        let mut parser = MessageParser::request(limits);
+        assert_eq!(parser.feed(format!("GET /{} HTTP/1.1", "a".repeat(40)).as_bytes()), Err(ParseError::StartLineTooLong));
+
+        let mut parser = MessageParser::request(limits);
+        assert_eq!(parser.feed(b"GET / HTTP/1.1\r\nA: 1\r\nB: 2\r\nC: 3\r\n\r\n"), Err(ParseError::TooManyHeaders));
+
+        let mut parser = MessageParser::request(limits);
+        let long_headers = format!("GET / HTTP/1.1\r\nA: {}\r\nB: {}\r\n\r\n", "x".repeat(28), "y".repeat(28));
+        assert_eq!(parser.feed(long_headers.as_bytes()), Err(ParseError::HeadersTooLarge));
+
+        let mut parser = MessageParser::request(limits);
+        assert_eq!(parser.feed(b"POST / HTTP/1.1\r\nContent-Length: 5\r\n\r\n"), Err(ParseError::BodyTooLarge));
+
+        let mut parser = MessageParser::request(limits);
+        assert_eq!(parser.feed(b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabc\r\n3\r\ndef\r\n"), Err(ParseError::BodyTooLarge));
+
+        assert_eq!(ParseError::StartLineTooLong.status(), HttpStatus::UriTooLong);
+        assert_eq!(ParseError::TooManyHeaders.status(), HttpStatus::RequestHeaderFieldsTooLarge);
+        assert_eq!(ParseError::BodyTooLarge.status(), HttpStatus::ContentTooLarge);
+    }
+}
@@ -0,0 +1,136 @@
+use std::io::{self, Write};
+
+use crate::http_builder::parser::BodyFraming;
+use crate::http_builder::{HttpHeader, HttpVersion};
+
+/// A response body sent piece by piece as it is produced, for bodies whose length isn't known
+/// when the head goes out
+///
+/// HTTP/1.1 clients get a chunked body, which can end with trailer fields, HTTP/1.0 clients get
+/// the body as it is and the end is marked by closing the connection. Started with
+/// `HttpResponse::stream`, a stream dropped without `finish` leaves a chunked body unterminated,
+/// so the client can tell it was cut short
+pub struct BodyStream<W: Write> {
+    writer: W,
+    framing: BodyFraming,
+}
+
+impl<W: Write> BodyStream<W> {
+    /// Wraps a writer the response head has already been written to
+    pub(crate) fn new(writer: W, framing: BodyFraming) -> Self {
+        BodyStream { writer, framing }
+    }
+
+    /// How the body is delimited for the client being answered
+    pub fn framing(&self) -> BodyFraming {
+        self.framing
+    }
+
+    /// Sends a piece of the body straight away, empty pieces are skipped since an empty chunk
+    /// would end a chunked body
+    pub fn send(&mut self, data: &[u8]) -> io::Result<()> {
+        if data.is_empty() {
+            return Ok(());
+        }
+        if self.framing == BodyFraming::Chunked {
+            write!(self.writer, "{:x}\r\n", data.len())?;
+            self.writer.write_all(data)?;
+            self.writer.write_all(b"\r\n")?;
+        } else {
+            self.writer.write_all(data)?;
+        }
+        self.writer.flush()
+    }
+
+    /// Ends the body
+    pub fn finish(self) -> io::Result<()> {
+        self.finish_with_trailers(&[])
+    }
+
+    /// Ends the body with trailer fields, e.g. a checksum of everything sent, which ought to be
+    /// announced up front with a `Trailer` header. Without chunked framing there is nowhere to
+    /// put them, so they are dropped
+    pub fn finish_with_trailers(mut self, trailers: &[HttpHeader]) -> io::Result<()> {
+        if self.framing == BodyFraming::Chunked {
+            self.writer.write_all(b"0\r\n")?;
+            for trailer in trailers {
+                write!(self.writer, "{}", trailer)?;
+            }
+            self.writer.write_all(b"\r\n")?;
+        }
+        self.writer.flush()
+    }
+}
+
+impl<W: Write> Write for BodyStream<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.send(buf)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Chunked for HTTP/1.1, HTTP/1.0 doesn't know it so the body runs until the connection closes
+pub fn streaming_framing(version: HttpVersion) -> BodyFraming {
+    match version {
+        HttpVersion::Http11 => BodyFraming::Chunked,
+        HttpVersion::Http10 => BodyFraming::CloseDelimited,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http_builder::parser::{MessageParser, ParseProgress, ParserLimits};
+    use crate::http_builder::{HttpResponse, HttpStatus, ResponseContext};
+    use crate::server::util::mime_types::MimeType;
+
+    fn stream_response(version: HttpVersion) -> Vec<u8> {
+        let context = ResponseContext { version, ..ResponseContext::default() };
+        let mut output = Vec::new();
+        let mut body = HttpResponse::with_context(HttpStatus::RequestOk, &context)
+            .header(HttpHeader::Custom("Trailer".to_string(), "X-Checksum".to_string()))
+            .stream(MimeType::PLAIN_TEXT, &mut output)
+            .unwrap();
+        body.send(b"hello").unwrap();
+        body.send(b"").unwrap();
+        body.write_all(b" world").unwrap();
+        body.finish_with_trailers(&[HttpHeader::Custom("X-Checksum".to_string(), "abc".to_string())]).unwrap();
+        output
+    }
+
+    #[test]
+    fn test_chunked_stream() {
+        let output = stream_response(HttpVersion::Http11);
+        let text = String::from_utf8(output.clone()).unwrap();
+        assert!(text.contains("Transfer-Encoding: chunked\r\n"));
+        assert!(!text.contains("Content-Length"));
+        assert!(text.ends_with("\r\n\r\n5\r\nhello\r\n6\r\n world\r\n0\r\nX-Checksum: abc\r\n\r\n"));
+
+        let mut parser = MessageParser::response(ParserLimits::default());
+        assert_eq!(parser.feed(&output), Ok(ParseProgress::Complete));
+        let message = parser.into_message().unwrap();
+        assert_eq!(message.head.framing, BodyFraming::Chunked);
+        assert_eq!(message.body, b"hello world");
+        assert_eq!(message.trailers.get("X-Checksum"), Some("abc".to_string()));
+    }
+
+    #[test]
+    fn test_close_delimited_stream() {
+        let output = stream_response(HttpVersion::Http10);
+        let text = String::from_utf8(output.clone()).unwrap();
+        assert!(text.starts_with("HTTP/1.0 200 OK\r\n"));
+        assert!(text.contains("Connection: close\r\n"));
+        assert!(text.ends_with("\r\n\r\nhello world"));
+
+        let mut parser = MessageParser::response(ParserLimits::default());
+        parser.feed(&output).unwrap();
+        assert_eq!(parser.finish(), Ok(ParseProgress::Complete));
+        let message = parser.into_message().unwrap();
+        assert_eq!(message.head.framing, BodyFraming::CloseDelimited);
+        assert_eq!(message.body, b"hello world");
+    }
+}
@@ -0,0 +1,3 @@
+pub mod html_builder;
+pub mod http_builder;
+pub mod server;
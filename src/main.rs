@@ -1,13 +1,11 @@
-mod html_builder;
-mod http_builder;
-mod server;
-
-use server::server::Server;
-use server::routes::Route;
-use server::directories::Directory;
-use server::responses::ok::{ respond_ok_abxy, respond_ok_id, respond_ok_memes, respond_ok };
-use server::responses::experimental::{ respond_ok_barcode, respond_ok_webgl };
-use http_builder::HttpMethod::GET;
+use http_server::server::server::Server;
+use http_server::server::routes::Route;
+use http_server::http_builder::compression::CompressionPolicy;
+use http_server::server::directories::Directory;
+use http_server::server::access_rules::AccessRules;
+use http_server::server::responses::ok::{ respond_ok_abxy, respond_ok_id, respond_ok_memes, respond_ok };
+use http_server::server::responses::experimental::{ respond_ok_barcode, respond_ok_webgl, respond_websocket_webgl };
+use http_server::http_builder::HttpMethod::GET;
 
 fn main() {
     let server = Server::new(vec![
@@ -16,11 +14,18 @@ fn main() {
         Route::new("/yourid/:id", GET, respond_ok_id),
         Route::new("/a/:b/x/:y", GET, respond_ok_abxy),
         Route::new("/webgl", GET, respond_ok_webgl),
+        Route::websocket("/webgl_live", respond_websocket_webgl),
         Route::new("/barcode/:data", GET, respond_ok_barcode),
     ],
     vec![
-        Directory::new("/", false),
-        Directory::new("/src/", true),
-    ]);
+        // the repository root also holds the manifest and notes, only the demo's scripts are served
+        Directory::new("/", false)
+            .with_access_rules(AccessRules::new()
+                .with_include_glob("hello_world.js")
+                .with_include_glob("webgl_test.js")),
+        Directory::new("/src/", true)
+            .with_access_rules(AccessRules::new().with_denied_extension("rs")),
+    ])
+    .with_compression(CompressionPolicy::new());
     server.run();
 }
@@ -0,0 +1,136 @@
+use crate::server::util::glob::glob_matches;
+use crate::server::util::uri::get_file_extension;
+
+/// What the client is told when a file is denied by the access rules
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DeniedResponse {
+    /// 404 Not Found, the default, doesn't reveal that the file exists
+    #[default]
+    NotFound,
+    /// 403 Forbidden
+    Forbidden,
+}
+
+/// Rules deciding which files inside a `Directory` may be served
+///
+/// Checked in order: hidden files, excluded globs, denied extensions, included globs and
+/// then allowed extensions. Hidden files (any path segment starting with `.`, e.g. `.git/config`
+/// or `.env`) are denied unless explicitly allowed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AccessRules {
+    allow_hidden_files: bool,
+    allowed_extensions: Vec<String>,
+    denied_extensions: Vec<String>,
+    include_globs: Vec<String>,
+    exclude_globs: Vec<String>,
+    denied_response: DeniedResponse,
+}
+
+impl AccessRules {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Serve dotfiles and files inside dot-directories
+    pub fn with_hidden_files_allowed(mut self) -> Self {
+        self.allow_hidden_files = true;
+        self
+    }
+
+    /// Only serve files with one of the allowed extensions, can be given several times
+    pub fn with_allowed_extension(mut self, extension: &str) -> Self {
+        self.allowed_extensions.push(extension.trim_start_matches('.').to_string());
+        self
+    }
+
+    /// Never serve files with this extension
+    pub fn with_denied_extension(mut self, extension: &str) -> Self {
+        self.denied_extensions.push(extension.trim_start_matches('.').to_string());
+        self
+    }
+
+    /// Only serve files whose path relative to the directory matches one of the included globs
+    pub fn with_include_glob(mut self, pattern: &str) -> Self {
+        self.include_globs.push(pattern.to_string());
+        self
+    }
+
+    /// Never serve files whose path relative to the directory matches this glob
+    pub fn with_exclude_glob(mut self, pattern: &str) -> Self {
+        self.exclude_globs.push(pattern.to_string());
+        self
+    }
+
+    /// Respond 403 instead of 404 for denied files
+    pub fn with_denied_response(mut self, denied_response: DeniedResponse) -> Self {
+        self.denied_response = denied_response;
+        self
+    }
+
+    pub fn denied_response(&self) -> DeniedResponse {
+        self.denied_response
+    }
+
+    /// Whether a file, given by its path relative to the directory, may be served
+    pub fn is_allowed(&self, relative_path: &str) -> bool {
+        if !self.allow_hidden_files && relative_path.split('/').any(|segment| segment.starts_with('.') && segment != ".") {
+            return false;
+        }
+        if self.exclude_globs.iter().any(|pattern| glob_matches(pattern, relative_path)) {
+            return false;
+        }
+
+        let extension = get_file_extension(relative_path);
+        if self.denied_extensions.iter().any(|denied| denied.eq_ignore_ascii_case(&extension)) {
+            return false;
+        }
+        if !self.include_globs.is_empty() && !self.include_globs.iter().any(|pattern| glob_matches(pattern, relative_path)) {
+            return false;
+        }
+        if !self.allowed_extensions.is_empty() && !self.allowed_extensions.iter().any(|allowed| allowed.eq_ignore_ascii_case(&extension)) {
+            return false;
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hidden_files() {
+        let rules = AccessRules::new();
+        assert!(!rules.is_allowed(".env"));
+        assert!(!rules.is_allowed(".git/config"));
+        assert!(!rules.is_allowed("app/.secrets/key.pem"));
+        assert!(rules.is_allowed("app/index.html"));
+
+        let rules = AccessRules::new().with_hidden_files_allowed();
+        assert!(rules.is_allowed(".well-known/security.txt"));
+    }
+
+    #[test]
+    fn test_extensions() {
+        let rules = AccessRules::new().with_denied_extension("rs");
+        assert!(!rules.is_allowed("main.rs"));
+        assert!(!rules.is_allowed("server/mod.RS"));
+        assert!(rules.is_allowed("main.js"));
+
+        let rules = AccessRules::new().with_allowed_extension(".js").with_allowed_extension("css");
+        assert!(rules.is_allowed("app.js"));
+        assert!(rules.is_allowed("style.css"));
+        assert!(!rules.is_allowed("Cargo.toml"));
+        assert!(!rules.is_allowed("README"));
+    }
+
+    #[test]
+    fn test_globs() {
+        let rules = AccessRules::new()
+            .with_include_glob("public/**")
+            .with_exclude_glob("public/drafts/**");
+        assert!(rules.is_allowed("public/index.html"));
+        assert!(!rules.is_allowed("public/drafts/post.html"));
+        assert!(!rules.is_allowed("private/index.html"));
+    }
+}
@@ -0,0 +1,186 @@
+use std::fmt;
+use std::time::{Duration, SystemTime};
+
+use crate::http_builder::HttpHeader;
+
+/// One year, the conventional max-age for content that never changes
+const IMMUTABLE_MAX_AGE: u64 = 31_536_000;
+
+/// Minimum length of a filename segment for it to count as a content hash
+const MIN_FINGERPRINT_LENGTH: usize = 8;
+
+/// A single `Cache-Control` response directive
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CacheDirective {
+    Public,
+    Private,
+    NoCache,
+    NoStore,
+    NoTransform,
+    MustRevalidate,
+    ProxyRevalidate,
+    Immutable,
+    MaxAge(u64),
+    SharedMaxAge(u64),
+    StaleWhileRevalidate(u64),
+    StaleIfError(u64),
+}
+
+impl fmt::Display for CacheDirective {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CacheDirective::Public => write!(f, "public"),
+            CacheDirective::Private => write!(f, "private"),
+            CacheDirective::NoCache => write!(f, "no-cache"),
+            CacheDirective::NoStore => write!(f, "no-store"),
+            CacheDirective::NoTransform => write!(f, "no-transform"),
+            CacheDirective::MustRevalidate => write!(f, "must-revalidate"),
+            CacheDirective::ProxyRevalidate => write!(f, "proxy-revalidate"),
+            CacheDirective::Immutable => write!(f, "immutable"),
+            CacheDirective::MaxAge(seconds) => write!(f, "max-age={}", seconds),
+            CacheDirective::SharedMaxAge(seconds) => write!(f, "s-maxage={}", seconds),
+            CacheDirective::StaleWhileRevalidate(seconds) => write!(f, "stale-while-revalidate={}", seconds),
+            CacheDirective::StaleIfError(seconds) => write!(f, "stale-if-error={}", seconds),
+        }
+    }
+}
+
+/// Caching policy for files served out of a `Directory`
+///
+/// Built up with the `with_*` methods, e.g.
+/// `CachePolicy::new().with_directive(CacheDirective::Public).with_expires_in(Duration::from_secs(3600))`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CachePolicy {
+    directives: Vec<CacheDirective>,
+    expires_in: Option<Duration>,
+    immutable_when_fingerprinted: bool,
+}
+
+impl CachePolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Shorthand for `no-store`, nothing is kept by any cache
+    pub fn no_store() -> Self {
+        Self::new().with_directive(CacheDirective::NoStore)
+    }
+
+    /// Shorthand for `public, max-age=<seconds>`
+    pub fn public_max_age(seconds: u64) -> Self {
+        Self::new()
+            .with_directive(CacheDirective::Public)
+            .with_directive(CacheDirective::MaxAge(seconds))
+    }
+
+    pub fn with_directive(mut self, directive: CacheDirective) -> Self {
+        self.directives.push(directive);
+        self
+    }
+
+    /// Send an `Expires` header this far in the future from the time of the response
+    pub fn with_expires_in(mut self, expires_in: Duration) -> Self {
+        self.expires_in = Some(expires_in);
+        self
+    }
+
+    /// Files whose name contains a content hash (`app.3f2a9c1b.js`, `index-5b7e1d20.css`)
+    /// are sent as `public, max-age=31536000, immutable` instead of the policy's own directives
+    pub fn with_immutable_when_fingerprinted(mut self) -> Self {
+        self.immutable_when_fingerprinted = true;
+        self
+    }
+
+    /// The Cache-Control and Expires headers for the given file, relative to `now`
+    pub fn headers(&self, file_name: &str, now: SystemTime) -> Vec<HttpHeader> {
+        let mut headers = Vec::new();
+
+        if self.immutable_when_fingerprinted && is_fingerprinted(file_name) {
+            headers.push(cache_control(&[
+                CacheDirective::Public,
+                CacheDirective::MaxAge(IMMUTABLE_MAX_AGE),
+                CacheDirective::Immutable,
+            ]));
+            headers.push(HttpHeader::Expires(now + Duration::from_secs(IMMUTABLE_MAX_AGE)));
+            return headers;
+        }
+
+        if !self.directives.is_empty() {
+            headers.push(cache_control(&self.directives));
+        }
+        if let Some(expires_in) = self.expires_in {
+            headers.push(HttpHeader::Expires(now + expires_in));
+        }
+        headers
+    }
+}
+
+fn cache_control(directives: &[CacheDirective]) -> HttpHeader {
+    HttpHeader::Custom(
+        "Cache-Control".to_string(),
+        directives.iter().map(|d| d.to_string()).collect::<Vec<String>>().join(", "),
+    )
+}
+
+/// Whether a file name carries a content hash between its stem and extension
+///
+/// a hash is a segment, separated by `.`, `-` or `_`, of at least 8 alphanumeric
+/// characters containing at least one digit (so `jquery.min.js` doesn't qualify)
+pub fn is_fingerprinted(file_name: &str) -> bool {
+    let file_name = file_name.rsplit('/').next().unwrap_or(file_name);
+    let Some((stem, _extension)) = file_name.rsplit_once('.') else {
+        return false;
+    };
+    stem.split(['.', '-', '_'])
+        .skip(1)
+        .any(|segment| segment.len() >= MIN_FINGERPRINT_LENGTH
+            && segment.chars().all(|c| c.is_ascii_alphanumeric())
+            && segment.chars().any(|c| c.is_ascii_digit()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::UNIX_EPOCH;
+
+    #[test]
+    fn test_is_fingerprinted() {
+        assert!(is_fingerprinted("app.3f2a9c1b.js"));
+        assert!(is_fingerprinted("static/index-5b7e1d20.css"));
+        assert!(is_fingerprinted("chunk_a1b2c3d4e5.js"));
+        assert!(!is_fingerprinted("app.js"));
+        assert!(!is_fingerprinted("jquery.min.js"));
+        assert!(!is_fingerprinted("my-stylesheet.css"));
+        assert!(!is_fingerprinted("3f2a9c1b"));
+    }
+
+    #[test]
+    fn test_policy_headers() {
+        let now = UNIX_EPOCH + Duration::from_secs(784111777);
+
+        let policy = CachePolicy::public_max_age(60).with_expires_in(Duration::from_secs(60));
+        assert_eq!(policy.headers("app.js", now), vec![
+            HttpHeader::Custom("Cache-Control".to_string(), "public, max-age=60".to_string()),
+            HttpHeader::Expires(now + Duration::from_secs(60)),
+        ]);
+        assert_eq!(policy.headers("app.js", now)[1].to_string(), "Expires: Sun, 06 Nov 1994 08:50:37 GMT\r\n");
+
+        assert_eq!(CachePolicy::new().headers("app.js", now), vec![]);
+        assert_eq!(CachePolicy::no_store().headers("app.js", now), vec![
+            HttpHeader::Custom("Cache-Control".to_string(), "no-store".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn test_immutable_when_fingerprinted() {
+        let now = UNIX_EPOCH;
+        let policy = CachePolicy::new()
+            .with_directive(CacheDirective::NoCache)
+            .with_immutable_when_fingerprinted();
+
+        assert_eq!(policy.headers("app.3f2a9c1b.js", now)[0],
+            HttpHeader::Custom("Cache-Control".to_string(), "public, max-age=31536000, immutable".to_string()));
+        assert_eq!(policy.headers("app.js", now),
+            vec![HttpHeader::Custom("Cache-Control".to_string(), "no-cache".to_string())]);
+    }
+}
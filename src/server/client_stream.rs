@@ -0,0 +1,90 @@
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+
+use crate::server::util::sendfile::send_file;
+
+/// A client's connection that remembers whether any of a response has been written to it
+///
+/// once it has, nothing else can be sent in its place, so a failure after that point
+/// can only be logged and the connection dropped
+#[derive(Debug)]
+pub struct ClientStream {
+    stream: TcpStream,
+    response_started: bool,
+}
+
+impl ClientStream {
+    pub fn new(stream: TcpStream) -> Self {
+        ClientStream { stream, response_started: false }
+    }
+
+    /// Whether any bytes of a final response have been written
+    pub fn response_started(&self) -> bool {
+        self.response_started
+    }
+
+    /// The underlying socket, e.g. to change its timeouts
+    pub fn get_ref(&self) -> &TcpStream {
+        &self.stream
+    }
+
+    /// Writes an informational (1xx) response, which doesn't count as starting the response
+    /// since the final one can still be anything
+    pub fn write_interim(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.stream.write_all(bytes)?;
+        self.stream.flush()
+    }
+
+    /// Sends `length` bytes of `file`, starting at `offset`, see `util::sendfile::send_file`
+    pub fn send_file(&mut self, file: &mut File, offset: u64, length: u64) -> io::Result<()> {
+        self.response_started |= length > 0;
+        send_file(&mut self.stream, file, offset, length)
+    }
+}
+
+impl Read for ClientStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.stream.read(buf)
+    }
+}
+
+impl Write for ClientStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.stream.write(buf)?;
+        self.response_started |= written > 0;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    fn connected_pair() -> (ClientStream, TcpStream) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (server, _) = listener.accept().unwrap();
+        (ClientStream::new(server), client)
+    }
+
+    #[test]
+    fn test_response_started_after_a_write() {
+        let (mut stream, mut client) = connected_pair();
+        stream.write_interim(b"HTTP/1.1 100 Continue\r\n\r\n").unwrap();
+        assert!(!stream.response_started());
+
+        stream.write_all(b"HTTP/1.1 200 OK\r\n\r\n").unwrap();
+        assert!(stream.response_started());
+        drop(stream);
+
+        let mut received = String::new();
+        client.read_to_string(&mut received).unwrap();
+        assert_eq!(received, "HTTP/1.1 100 Continue\r\n\r\nHTTP/1.1 200 OK\r\n\r\n");
+    }
+}
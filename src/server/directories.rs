@@ -1,4 +1,29 @@
-use super::util::uri::sanitize;
+use std::time::SystemTime;
+
+use crate::http_builder::HttpHeader;
+
+use super::access_rules::AccessRules;
+use super::cache_policy::CachePolicy;
+use super::util::glob::glob_matches;
+use super::util::uri::{get_file_extension, sanitize};
+
+/// Which files inside a directory a cache policy applies to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CacheRuleMatcher {
+    /// File extension without the leading dot, e.g. `js`
+    Extension(String),
+    /// Glob matched against the path relative to the directory, e.g. `images/**`
+    Glob(String),
+}
+
+impl CacheRuleMatcher {
+    fn matches(&self, relative_path: &str) -> bool {
+        match self {
+            CacheRuleMatcher::Extension(extension) => get_file_extension(relative_path).eq_ignore_ascii_case(extension),
+            CacheRuleMatcher::Glob(pattern) => glob_matches(pattern, relative_path),
+        }
+    }
+}
 
 /// <h1>Directory</h1> 
 /// Structure to represent a directory.
@@ -6,30 +31,211 @@ use super::util::uri::sanitize;
 pub struct Directory {
     //TODO: check if path is valid otherwise return an error (non-empty)
     path: String,
-    pub allow_subdirectories: bool
+    max_depth: Option<usize>,
+    cache_policy: CachePolicy,
+    cache_rules: Vec<(CacheRuleMatcher, CachePolicy)>,
+    access_rules: AccessRules,
+    nosniff: bool,
 }
 
 impl Directory {
+    /// `allow_subdirectories` false only serves files directly inside the directory,
+    /// true serves files nested at any depth, see `with_max_depth` for anything in between
     pub fn new(path: &str, allow_subdirectories: bool) -> Self {
         Self {
             path: path.to_string(),
-            allow_subdirectories
+            max_depth: if allow_subdirectories { None } else { Some(0) },
+            cache_policy: CachePolicy::new(),
+            cache_rules: Vec::new(),
+            access_rules: AccessRules::new(),
+            nosniff: false,
+        }
+    }
+
+    /// How many levels of subdirectories may be served, 0 is only files directly inside the directory
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = Some(max_depth);
+        self
+    }
+
+    /// Serve files nested at any depth below the directory
+    pub fn with_unlimited_depth(mut self) -> Self {
+        self.max_depth = None;
+        self
+    }
+
+    pub fn max_depth(&self) -> Option<usize> {
+        self.max_depth
+    }
+
+    /// Whether a file `depth` subdirectories below this directory may be served
+    pub fn allows_depth(&self, depth: usize) -> bool {
+        self.max_depth.is_none_or(|max_depth| depth <= max_depth)
+    }
+
+    /// Subdirectory depth of the requested file below this directory, see `subdirectory_depth`
+    pub fn depth_of(&self, requested_path: &str) -> Option<usize> {
+        subdirectory_depth(requested_path, &self.path)
+    }
+
+    /// Rules restricting which files in the directory may be served, see `AccessRules`
+    pub fn with_access_rules(mut self, access_rules: AccessRules) -> Self {
+        self.access_rules = access_rules;
+        self
+    }
+
+    pub fn access_rules(&self) -> &AccessRules {
+        &self.access_rules
+    }
+
+    /// Whether the requested file may be served according to the access rules
+    pub fn is_accessible(&self, requested_path: &str) -> bool {
+        match self.relative_path(requested_path) {
+            Some(relative_path) => self.access_rules.is_allowed(&relative_path),
+            None => false,
         }
     }
 
+    /// Type files by their extension alone, unknown ones are sent as `application/octet-stream`
+    /// instead of being sniffed, and tell browsers not to sniff either with `X-Content-Type-Options: nosniff`
+    pub fn with_nosniff(mut self) -> Self {
+        self.nosniff = true;
+        self
+    }
+
+    pub fn nosniff(&self) -> bool {
+        self.nosniff
+    }
+
+    /// Cache policy for every file in the directory not matched by a more specific rule
+    pub fn with_cache_policy(mut self, policy: CachePolicy) -> Self {
+        self.cache_policy = policy;
+        self
+    }
+
+    /// Cache policy for files with the given extension, e.g. `"js"`
+    pub fn with_cache_policy_for_extension(mut self, extension: &str, policy: CachePolicy) -> Self {
+        let extension = extension.trim_start_matches('.').to_string();
+        self.cache_rules.push((CacheRuleMatcher::Extension(extension), policy));
+        self
+    }
+
+    /// Cache policy for files whose path relative to the directory matches the glob, e.g. `"fonts/**"`
+    pub fn with_cache_policy_for_glob(mut self, pattern: &str, policy: CachePolicy) -> Self {
+        self.cache_rules.push((CacheRuleMatcher::Glob(pattern.to_string()), policy));
+        self
+    }
+
     pub fn path(&self) -> String {
         self.path.clone()
     }
+
+    /// Path of the requested file relative to this directory, without a leading slash
+    ///
+    /// empty segments are dropped, so `/static//css/app.css` in `/static` is `css/app.css`
+    pub fn relative_path(&self, requested_path: &str) -> Option<String> {
+        let requested = path_segments(requested_path);
+        let directory = path_segments(&self.path);
+        if !requested.starts_with(&directory) {
+            return None;
+        }
+        Some(requested[directory.len()..].join("/"))
+    }
+
+    /// The cache policy for a file, the first matching rule wins, in the order the rules were added
+    pub fn cache_policy_for(&self, requested_path: &str) -> &CachePolicy {
+        let relative_path = self.relative_path(requested_path).unwrap_or_default();
+        self.cache_rules.iter()
+            .find(|(matcher, _)| matcher.matches(&relative_path))
+            .map(|(_, policy)| policy)
+            .unwrap_or(&self.cache_policy)
+    }
+
+    /// The Cache-Control and Expires headers to send with a file from this directory
+    pub fn cache_headers(&self, requested_path: &str) -> Vec<HttpHeader> {
+        self.cache_policy_for(requested_path).headers(requested_path, SystemTime::now())
+    }
 }
 
-pub fn directory_is_first_level(requested_path: &str, allowed_path: &str) -> bool {
+/// The non-empty segments of a path, so leading, trailing and repeated slashes don't matter
+fn path_segments(path: &str) -> Vec<&str> {
+    path.split('/').filter(|segment| !segment.is_empty()).collect()
+}
+
+/// How many subdirectories below `directory_path` the requested file is
+///
+/// 0 for a file directly inside the directory, 1 for a file in a child directory and so on.
+/// None if the path isn't inside the directory, names the directory itself, or doesn't sanitize.
+pub fn subdirectory_depth(requested_path: &str, directory_path: &str) -> Option<usize> {
     //sanitize the path before comparing
-    let sanitized_requested_path = sanitize(requested_path).unwrap_or("".to_string());
-    let requested_path = sanitized_requested_path.split('/').collect::<Vec<&str>>();
-    let allowed_path = allowed_path.split('/').collect::<Vec<&str>>();
+    let sanitized_requested_path = sanitize(requested_path).ok()?;
+    let requested = path_segments(&sanitized_requested_path);
+    let directory = path_segments(directory_path);
+
+    if requested.len() <= directory.len() || !requested.starts_with(&directory) {
+        return None;
+    }
+    Some(requested.len() - directory.len() - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_subdirectory_depth() {
+        assert_eq!(subdirectory_depth("/index.html", "/"), Some(0));
+        assert_eq!(subdirectory_depth("/src/main.rs", "/"), Some(1));
+        assert_eq!(subdirectory_depth("/src/server/mod.rs", "/"), Some(2));
+        assert_eq!(subdirectory_depth("/src/main.rs", "/src/"), Some(0));
+        assert_eq!(subdirectory_depth("/src/main.rs", "/src"), Some(0));
+        assert_eq!(subdirectory_depth("/src/server/util/uri.rs", "/src/"), Some(2));
+    }
+
+    #[test]
+    fn test_subdirectory_depth_edge_cases() {
+        //empty segments and trailing slashes don't add depth
+        assert_eq!(subdirectory_depth("/src//main.rs", "/src/"), Some(0));
+        assert_eq!(subdirectory_depth("//src/./server/mod.rs", "/src//"), Some(1));
+        assert_eq!(subdirectory_depth("/src/server/", "/src/"), Some(0));
+
+        //not inside the directory, or the directory itself
+        assert_eq!(subdirectory_depth("/srcfoo/main.rs", "/src/"), None);
+        assert_eq!(subdirectory_depth("/other/main.rs", "/src/"), None);
+        assert_eq!(subdirectory_depth("/src/", "/src/"), None);
+        assert_eq!(subdirectory_depth("/src", "/src/"), None);
+
+        //traversal is rejected by sanitize
+        assert_eq!(subdirectory_depth("/src/../Cargo.toml", "/src/"), None);
+    }
+
+    #[test]
+    fn test_max_depth() {
+        let directory = Directory::new("/", false);
+        assert!(directory.allows_depth(0));
+        assert!(!directory.allows_depth(1));
+
+        let directory = Directory::new("/", true);
+        assert!(directory.allows_depth(100));
+
+        let directory = Directory::new("/", true).with_max_depth(2);
+        assert!(directory.allows_depth(2));
+        assert!(!directory.allows_depth(3));
+        assert!(directory.with_unlimited_depth().allows_depth(3));
+    }
+
+    #[test]
+    fn test_cache_policy_for() {
+        let directory = Directory::new("/static/", true)
+            .with_cache_policy(CachePolicy::no_store())
+            .with_cache_policy_for_glob("fonts/**", CachePolicy::public_max_age(86400))
+            .with_cache_policy_for_extension(".js", CachePolicy::public_max_age(60));
 
-    if requested_path.len() > allowed_path.len() {
-        return false;
+        assert_eq!(directory.relative_path("/static/fonts/a.woff2"), Some("fonts/a.woff2".to_string()));
+        assert_eq!(directory.cache_policy_for("/static/fonts/a.woff2"), &CachePolicy::public_max_age(86400));
+        assert_eq!(directory.cache_policy_for("/static/js/app.JS"), &CachePolicy::public_max_age(60));
+        //first matching rule wins
+        assert_eq!(directory.cache_policy_for("/static/fonts/loader.js"), &CachePolicy::public_max_age(86400));
+        assert_eq!(directory.cache_policy_for("/static/index.html"), &CachePolicy::no_store());
     }
-    true
 }
@@ -0,0 +1,138 @@
+use std::fmt;
+use std::io;
+
+use crate::http_builder::HttpStatus;
+use crate::http_builder::parser::ParseError;
+
+/// Anything that can go wrong while answering a request
+///
+/// handlers return it with `?`, and the server turns it into a response with the matching status
+#[derive(Debug)]
+pub enum ServerError {
+    /// The request couldn't be parsed
+    Parse(ParseError),
+    /// The request parsed but isn't acceptable, e.g. a missing Host header or a malformed route
+    BadRequest(String),
+    /// No route or file matches the request
+    NotFound(String),
+    /// None of the representations a handler offers are acceptable to the client
+    NotAcceptable(String),
+    /// The file exists but the server's rules don't allow it to be served
+    Forbidden(String),
+    /// Reading a file or talking to the client failed
+    Io(io::Error),
+    /// The client took too long to send the request
+    Timeout,
+    /// A handler gave up with a status of its choosing
+    Handler { status: HttpStatus, message: String },
+}
+
+impl ServerError {
+    pub fn handler(status: HttpStatus, message: &str) -> Self {
+        ServerError::Handler { status, message: message.to_string() }
+    }
+
+    /// The status the client should get for this error
+    pub fn status(&self) -> HttpStatus {
+        match self {
+            ServerError::Parse(e) => e.status(),
+            ServerError::BadRequest(_) => HttpStatus::BadRequest,
+            ServerError::NotFound(_) => HttpStatus::NotFound,
+            ServerError::NotAcceptable(_) => HttpStatus::NotAcceptable,
+            ServerError::Forbidden(_) => HttpStatus::Forbidden,
+            ServerError::Io(e) => match e.kind() {
+                io::ErrorKind::NotFound => HttpStatus::NotFound,
+                io::ErrorKind::PermissionDenied => HttpStatus::Forbidden,
+                _ => HttpStatus::InternalServerError,
+            },
+            ServerError::Timeout => HttpStatus::RequestTimeout,
+            ServerError::Handler { status, .. } => status.clone(),
+        }
+    }
+
+    /// The message sent to the client, internal details are left for the log
+    pub fn message(&self) -> String {
+        match self {
+            ServerError::Parse(e) => e.to_string(),
+            ServerError::BadRequest(message) | ServerError::NotFound(message) | ServerError::Forbidden(message)
+                | ServerError::NotAcceptable(message) => message.clone(),
+            ServerError::Io(e) => match e.kind() {
+                io::ErrorKind::NotFound => "File Not Found".to_string(),
+                io::ErrorKind::PermissionDenied => "Forbidden, Access Denied".to_string(),
+                _ => "Something went wrong! Contact the server administrator.".to_string(),
+            },
+            ServerError::Timeout => "Timed out waiting for the request".to_string(),
+            ServerError::Handler { message, .. } => message.clone(),
+        }
+    }
+
+    /// Whether the client has gone away, so there is no one to respond to
+    pub fn is_disconnect(&self) -> bool {
+        matches!(self, ServerError::Io(e) if matches!(
+            e.kind(),
+            io::ErrorKind::BrokenPipe | io::ErrorKind::ConnectionReset | io::ErrorKind::ConnectionAborted
+        ))
+    }
+}
+
+impl fmt::Display for ServerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ServerError::Parse(e) => write!(f, "Malformed request: {}", e),
+            ServerError::BadRequest(message) => write!(f, "Bad request: {}", message),
+            ServerError::NotFound(message) => write!(f, "Not found: {}", message),
+            ServerError::NotAcceptable(message) => write!(f, "Not acceptable: {}", message),
+            ServerError::Forbidden(message) => write!(f, "Forbidden: {}", message),
+            ServerError::Io(e) => write!(f, "I/O error: {}", e),
+            ServerError::Timeout => write!(f, "Timed out reading the request"),
+            ServerError::Handler { status, message } => write!(f, "Handler failed with {}: {}", status, message),
+        }
+    }
+}
+
+impl std::error::Error for ServerError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ServerError::Parse(e) => Some(e),
+            ServerError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ServerError {
+    fn from(error: io::Error) -> Self {
+        ServerError::Io(error)
+    }
+}
+
+impl From<ParseError> for ServerError {
+    fn from(error: ParseError) -> Self {
+        ServerError::Parse(error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_mapping() {
+        assert_eq!(ServerError::from(ParseError::TooManyHeaders).status(), HttpStatus::RequestHeaderFieldsTooLarge);
+        assert_eq!(ServerError::BadRequest("x".to_string()).status(), HttpStatus::BadRequest);
+        assert_eq!(ServerError::from(io::Error::from(io::ErrorKind::NotFound)).status(), HttpStatus::NotFound);
+        assert_eq!(ServerError::from(io::Error::from(io::ErrorKind::PermissionDenied)).status(), HttpStatus::Forbidden);
+        assert_eq!(ServerError::from(io::Error::other("disk on fire")).status(), HttpStatus::InternalServerError);
+        assert_eq!(ServerError::Timeout.status(), HttpStatus::RequestTimeout);
+        assert_eq!(ServerError::handler(HttpStatus::Conflict, "already exists").status(), HttpStatus::Conflict);
+    }
+
+    #[test]
+    fn test_messages_hide_internals() {
+        let error = ServerError::from(io::Error::other("/var/secret/path is corrupt"));
+        assert!(!error.message().contains("secret"));
+        assert!(error.to_string().contains("secret"));
+        assert!(ServerError::from(io::Error::from(io::ErrorKind::BrokenPipe)).is_disconnect());
+        assert!(!ServerError::Timeout.is_disconnect());
+    }
+}
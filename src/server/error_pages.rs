@@ -0,0 +1,52 @@
+use std::collections::HashMap;
+use std::io;
+use std::path::PathBuf;
+
+use crate::server::client_stream::ClientStream;
+use crate::http_builder::{HttpStatus, ResponseContext};
+
+/// Writes a complete error response for the status, given the message the server would have sent,
+/// building it with `HttpResponse::with_context` keeps the server's headers
+pub type ErrorHandler = fn(&mut ClientStream, &ResponseContext, &HttpStatus, &str) -> io::Result<()>;
+
+/// A replacement for the built in response to an error status
+#[derive(Debug, Clone)]
+pub enum ErrorPage {
+    /// A static file, sent with the error status and the type of its extension
+    File(PathBuf),
+    Handler(ErrorHandler),
+}
+
+/// Custom error pages by status code, statuses without one get the built in response
+#[derive(Debug, Clone, Default)]
+pub struct ErrorPages {
+    pages: HashMap<u16, ErrorPage>,
+}
+
+impl ErrorPages {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_page(mut self, status: HttpStatus, page: ErrorPage) -> Self {
+        self.pages.insert(status.code(), page);
+        self
+    }
+
+    /// Serve the file at `path`, relative to the working directory, for this status
+    pub fn with_file(self, status: HttpStatus, path: &str) -> Self {
+        self.with_page(status, ErrorPage::File(PathBuf::from(path)))
+    }
+
+    pub fn with_handler(self, status: HttpStatus, handler: ErrorHandler) -> Self {
+        self.with_page(status, ErrorPage::Handler(handler))
+    }
+
+    pub fn get(&self, status: &HttpStatus) -> Option<&ErrorPage> {
+        self.pages.get(&status.code())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pages.is_empty()
+    }
+}
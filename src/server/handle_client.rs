@@ -1,116 +1,291 @@
 use std::collections::HashMap;
-use std::fs::read_to_string;
-use std::net::TcpStream;
+use std::fs::File;
 use std::io::{self, Read};
-use std::str::FromStr;
-use crate::server::util::mime_types::from_file_extension;
+use std::time::Duration;
+use std::sync::Arc;
+use crate::server::client_stream::ClientStream;
+use crate::server::util::mime_types::{MimeType, MimeTypes};
+use crate::server::util::sniff::sniff_file;
+use crate::server::util::conditional::{ evaluate_preconditions, if_range_matches, FileValidators, Precondition };
+use crate::server::util::precompressed::{ find_precompressed, has_precompressed_sibling };
+use crate::server::util::range::{ parse_range_header, RangeError };
 use crate::server::util::request_validation::{ validate_header, validate_request_line };
-use crate::server::responses::{ error::{ respond_bad_request, respond_not_found, respond_forbidden, respond_internal_server_error}, ok::respond_ok_with_body_and_type };
-use crate::http_builder::{HttpRequest, HttpRequestLine, HttpMethod, HttpHeader};
+use crate::server::responses::conditional::respond_not_modified;
+use crate::server::responses::partial::{ respond_multipart_byteranges, respond_ok_with_ranges_accepted, respond_partial_content, respond_range_not_satisfiable };
+use crate::http_builder::{HttpRequest, HttpResponse, HttpMethod, HttpHeader, HttpStatus::{Continue, ExpectationFailed, PreconditionFailed, RequestOk}, HttpVersion, ResponseContext};
+use crate::http_builder::parser::{MessageParser, ParseError, ParseProgress, ParserLimits, HttpMessage, StartLine};
+use crate::http_builder::compression::{compress, negotiated_coding, varies_on_encoding};
+use crate::server::error::ServerError;
+use crate::server::error_pages::ErrorPages;
+use crate::server::responses::error::{respond_error_with_context, ErrorContext};
 use crate::server::util::uri::get_file_extension;
 use crate::server::routes::Route;
-use crate::server::directories::{ Directory, directory_is_first_level };
+use crate::server::access_rules::DeniedResponse;
+use crate::server::directories::Directory;
 
 use super::responses::experimental::respond_redirect;
 //use crate::server::util::externals::ExternalRequest;
 
-pub fn read_in_request(stream: &mut TcpStream) -> io::Result<String> {
-
-    let mut buffer = Vec::new();
-    let mut local_buf = [0; 1024];
+/// How long a client may take between sending bytes of its request
+const REQUEST_READ_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The server's settings every connection is handled with
+#[derive(Debug, Clone, Default)]
+pub struct ConnectionConfig {
+    pub limits: ParserLimits,
+    /// What every response starts from, before what the request asks for is known
+    pub response_context: ResponseContext,
+    /// Types files are served as, the built in table with the server's overrides
+    pub mime_types: Arc<MimeTypes>,
+    pub error_pages: Arc<ErrorPages>,
+}
 
-    // Read the request
-    loop {
-        let size = stream.read(&mut local_buf)?;
-        if size == 0 {
-            break;
+impl ConnectionConfig {
+    /// What error responses to `request` are written with, `None` before it has been parsed
+    pub fn error_context(&self, request: Option<&HttpRequest>) -> ErrorContext {
+        ErrorContext {
+            response: request.map_or_else(|| self.response_context.clone(), |request| request.response_context().clone()),
+            preferences: request.map(HttpRequest::preferences).unwrap_or_default(),
+            pages: self.error_pages.clone(),
+            mime_types: self.mime_types.clone(),
         }
-        buffer.extend_from_slice(&local_buf[..size]);
+    }
+}
 
-        // Check if we have received double CRLF, indicating the end of the header of the request
-        if buffer.windows(4).any(|window| window == b"\r\n\r\n") {
-            break;
-        }
+/// Read a whole request, feeding the parser as bytes arrive
+pub fn read_in_request(stream: &mut ClientStream, limits: ParserLimits) -> Result<HttpMessage, ServerError> {
+    let parser = read_request_head(stream, limits)?;
+    read_request_body(stream, parser)
+}
+
+/// Read until the start line and headers are in, the body may not have been sent yet
+fn read_request_head(stream: &mut ClientStream, limits: ParserLimits) -> Result<MessageParser, ServerError> {
+    let mut parser = MessageParser::request(limits);
+    while read_into_parser(stream, &mut parser)? == ParseProgress::NeedMore {}
+    Ok(parser)
+}
+
+/// Read the rest of a request whose head has been read
+fn read_request_body(stream: &mut ClientStream, mut parser: MessageParser) -> Result<HttpMessage, ServerError> {
+    while parser.progress() != ParseProgress::Complete {
+        read_into_parser(stream, &mut parser)?;
     }
+    Ok(parser.into_message().expect("parser reported a complete message"))
+}
 
-    String::from_utf8(buffer).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+fn read_into_parser(stream: &mut ClientStream, parser: &mut MessageParser) -> Result<ParseProgress, ServerError> {
+    let mut local_buf = [0; 1024];
+    let size = match stream.read(&mut local_buf) {
+        Ok(size) => size,
+        Err(e) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => return Err(ServerError::Timeout),
+        Err(e) => return Err(e.into()),
+    };
+    if size == 0 {
+        return Err(ParseError::UnexpectedEof.into());
+    }
+    Ok(parser.feed(&local_buf[..size])?)
 }
 
-fn handle_file_case(mut stream: &mut TcpStream, path: &str) -> io::Result<()> {
-    let path = path.chars().skip(1).collect::<String>();
-    let file_contents = read_to_string(&path);
-    match file_contents {
-                Ok(contents) => {
-                    return respond_ok_with_body_and_type(&mut stream, &contents, 
-                        from_file_extension(
-                            &get_file_extension(&path)
-                        )
-                    );
-                }
-                Err(ref e) if e.kind() == io::ErrorKind::NotFound => {
-                    // File not found, send 404 response.
-                    return respond_not_found(&mut stream, "File Not Found")
-                }
-                Err(ref e) if e.kind() == io::ErrorKind::PermissionDenied => {
-                    // Permission denied, send 403 response.
-                    return respond_forbidden(&mut stream, "Forbidden, Access Denied")
-                }
-                Err(_) => {
-                    //if the path points to nothing, return 404
-                    return respond_internal_server_error(&mut stream, "Something went wrong! Contact the server administrator.")
-                    //add logic here later for 403 Forbidden
-                }
-            }
+/// Whether the client is waiting for `100 Continue` before it sends the body,
+/// any other expectation is refused with `417 Expectation Failed`
+fn expects_continue(request: &HttpRequest) -> Result<bool, ServerError> {
+    // HTTP/1.0 has no interim responses, so its clients can't be waiting for one
+    if request.version() != HttpVersion::Http11 {
+        return Ok(false);
+    }
+    let expectations = request.headers().get_list("Expect");
+    if expectations.iter().any(|expectation| !expectation.eq_ignore_ascii_case("100-continue")) {
+        return Err(ServerError::handler(ExpectationFailed, "Only 100-continue expectations are supported"));
+    }
+    Ok(!expectations.is_empty())
+}
+
+/// Tell the client to go ahead and send the body, an interim response so the final one
+/// can still be anything, including an error
+fn write_continue(stream: &mut ClientStream) -> io::Result<()> {
+    stream.write_interim(format!("{} {}\r\n\r\n", HttpVersion::Http11, Continue).as_bytes())
+}
+
+/// Whether a `Range` header may be honoured given the request's `If-Range` precondition
+///
+/// if the validator no longer matches the file may have changed, so it is served whole
+fn if_range_allows_partial(request: &HttpRequest, validators: &FileValidators) -> bool {
+    match request.get_header("If-Range") {
+        Some(if_range) => if_range_matches(&if_range, validators),
+        None => true,
+    }
 }
 
-pub fn handle_client(mut stream: TcpStream, routes: &[Route], directories: &[Directory]) -> io::Result<()> {
+fn handle_file_case(stream: &mut ClientStream, request: &HttpRequest, directory: &Directory, path: &str, mime_types: &MimeTypes) -> Result<(), ServerError> {
+    let path = path.chars().skip(1).collect::<String>();
+    // the type is always that of the original file, even when a compressed sibling is sent
+    let content_type = match mime_types.get(&get_file_extension(&path)) {
+        Some(content_type) => content_type,
+        None if directory.nosniff() => MimeType::OCTET_STREAM,
+        None => sniff_file(&path).unwrap_or(MimeType::OCTET_STREAM),
+    };
+
+    let precompressed = find_precompressed(&path, request.get_header("Accept-Encoding").as_deref());
+    let served_path = precompressed.as_ref().map_or(&path, |(sibling, _)| sibling);
+
+    let mut file = File::open(served_path)?;
+    let metadata = file.metadata()?;
+    // a path naming a subdirectory rather than a file
+    if metadata.is_dir() {
+        return Err(ServerError::NotFound("File Not Found".to_string()));
+    }
+    let total_length = metadata.len();
+    // without a precompressed sibling the file may be compressed on the fly, except for range
+    // requests, whose ranges are always of the file as it is on disk
+    let dynamic_coding = match precompressed {
+        None if !request.headers().contains("Range") => negotiated_coding(request.response_context(), &content_type, total_length),
+        _ => None,
+    };
+    let coding = precompressed.as_ref().map(|(_, coding)| *coding).or(dynamic_coding);
+    let mut validators = FileValidators::from_metadata(&metadata);
+    if let Some(coding) = coding {
+        validators = validators.for_content_coding(coding.token());
+    }
+
+    // headers describing the file, sent with every successful or 304 response
+    let mut file_headers = validators.headers();
+    file_headers.extend(directory.cache_headers(&request.path()));
+    if let Some(coding) = coding {
+        file_headers.push(HttpHeader::Custom("Content-Encoding".to_string(), coding.to_string()));
+    }
+    if precompressed.is_some() || has_precompressed_sibling(&path) || varies_on_encoding(request.response_context(), &content_type, total_length) {
+        file_headers.push(HttpHeader::Custom("Vary".to_string(), "Accept-Encoding".to_string()));
+    }
+    if directory.nosniff() {
+        file_headers.push(HttpHeader::Custom("X-Content-Type-Options".to_string(), "nosniff".to_string()));
+    }
 
-    let request_str = read_in_request(&mut stream).expect("Failed to read request");
 
-    let mut header_lines: Vec<&str> = request_str.lines().collect();
+    match evaluate_preconditions(request, &validators) {
+        Precondition::Proceed => {}
+        Precondition::NotModified => return Ok(respond_not_modified(stream, request, file_headers)?),
+        Precondition::Failed => return Err(ServerError::handler(PreconditionFailed, "Precondition Failed")),
+    }
 
-    if header_lines.is_empty() {
-        return respond_bad_request(&mut stream, "Empty request");
+    if request.method() == HttpMethod::GET && if_range_allows_partial(request, &validators)
+        && let Some(range_header) = request.get_header("Range") {
+        match parse_range_header(&range_header, total_length) {
+            Ok(ranges) if ranges.len() == 1 => {
+                return Ok(respond_partial_content(stream, request, &mut file, content_type, &ranges[0], total_length, file_headers)?);
+            }
+            Ok(ranges) => {
+                return Ok(respond_multipart_byteranges(stream, request, &mut file, &ranges, content_type, total_length, file_headers)?);
+            }
+            Err(RangeError::Unsatisfiable) => return Ok(respond_range_not_satisfiable(stream, request, total_length)?),
+            // an unparseable Range header is ignored and the whole file is served
+            Err(RangeError::Malformed) => {}
+        }
     }
-    // Parse the request header
-    let request_line = header_lines[0];
-    header_lines.remove(0);
 
-    let mut headers = Vec::new();
-    for header in &header_lines {
-        if !header.is_empty() {
-            headers.push(HttpHeader::from_str(header).expect("Invalid header format"));            
+    if let Some(coding) = dynamic_coding {
+        let mut contents = Vec::with_capacity(total_length as usize);
+        file.read_to_end(&mut contents)?;
+        let compressed = compress(coding, &contents).expect("only codings the server can produce are negotiated");
+        let mut response = HttpResponse::for_request(RequestOk, request).bytes(compressed, content_type);
+        for header in file_headers {
+            response = response.header(header);
         }
+        return Ok(response.write_to(stream)?);
     }
 
-    let mut request_method_and_path : Vec<&str> = request_line.splitn(3, ' ').collect();
-    request_method_and_path.pop();
-    let mut request = HttpRequest::new(
-        HttpRequestLine::new(
-            HttpMethod::from_str(request_method_and_path[0]).unwrap_or(HttpMethod::GET),
-            request_method_and_path[1]
-        )
-    );
+    Ok(respond_ok_with_ranges_accepted(stream, request, &mut file, total_length, content_type, file_headers)?)
+}
+
+/// Answer the request on a connection, responding with the matching status if anything fails
+///
+/// errors are only returned when they are worth logging, server errors and failures
+/// after the response started, client errors are handled once they've been answered.
+/// `request` is filled in once the request's head has been parsed, so the caller can
+/// log against it even if a handler panics
+pub fn handle_client(stream: &mut ClientStream, routes: &[Route], directories: &[Directory], config: &ConnectionConfig, request: &mut Option<HttpRequest>) -> Result<(), ServerError> {
+    stream.get_ref().set_read_timeout(Some(REQUEST_READ_TIMEOUT))?;
+
+    let error = match handle_request(stream, routes, directories, config, request) {
+        Ok(()) => return Ok(()),
+        Err(error) => error,
+    };
+    if error.is_disconnect() || stream.response_started() {
+        return Err(error);
+    }
+    respond_error_with_context(stream, &config.error_context(request.as_ref()), &error.message(), error.status())?;
+    if error.status().is_server_error() {
+        return Err(error);
+    }
+    Ok(())
+}
 
-    for header in headers {
-        request.add_header(header);
+fn handle_request(stream: &mut ClientStream, routes: &[Route], directories: &[Directory], config: &ConnectionConfig, parsed: &mut Option<HttpRequest>) -> Result<(), ServerError> {
+    let parser = read_request_head(stream, config.limits)?;
+    let head = parser.head().expect("the head has been read");
+
+    let request_line = match &head.start_line {
+        StartLine::Request { method, target, version } => format!("{} {} {}", method, target, version),
+        StartLine::Response { .. } => unreachable!("a request parser only produces request lines"),
+    };
+    let header_lines: Vec<String> = head.headers.iter()
+        .map(|header| format!("{}: {}", header.name(), header.value()))
+        .collect();
+    let request = parsed.insert(head.to_request().expect("parsed as a request"));
+
+    // each connection serves a single request, so a client expecting to reuse it is told it will close
+    let mut context = config.response_context.clone();
+    context.version = request.version();
+    context.close_connection = request.keep_alive();
+    context.content_coding = context.compression.as_ref()
+        .and_then(|policy| policy.negotiate(request.headers().accept_encoding().as_deref()));
+    request.set_response_context(context);
+
+    if request.version() == HttpVersion::Http11 && request.headers().get_all("Host").len() != 1 {
+        return Err(ServerError::BadRequest("HTTP/1.1 requests need exactly one Host header".to_string()));
     }
 
     // Validate the request line
-    if let Err(err) = validate_request_line(request_line) {
-        return respond_bad_request(&mut stream, &err);
-    }
+    validate_request_line(&request_line).map_err(ServerError::BadRequest)?;
 
     // Validate the header
     for line in header_lines {
-        if let Err(err) = validate_header(line) {
-            return respond_bad_request(&mut stream, &err);
-        }
+        validate_header(&line).map_err(ServerError::BadRequest)?;
     }
 
     // TODO: fix sending external requests
 
+    let expects_continue = expects_continue(request)?;
+    let target = resolve_target(request, routes, directories)?;
+    if let Target::Route(route, _) = &target {
+        route.check(request)?;
+    }
+
+    // the body is only read once the request is known to be one that will be answered,
+    // a body over the size limit was already refused when its Content-Length was parsed
+    if parser.progress() != ParseProgress::Complete {
+        if expects_continue {
+            write_continue(stream)?;
+        }
+        request.set_body(read_request_body(stream, parser)?.body);
+    }
+
+    match target {
+        Target::Route(route, params) => route.call(stream, request, params),
+        Target::Redirect(correct_path) => Ok(respond_redirect(stream, &correct_path)?),
+        Target::File(directory) => handle_file_case(stream, request, directory, &request.path(), &config.mime_types),
+    }
+}
+
+/// What a request is answered by, worked out from its head alone
+enum Target<'a> {
+    Route(&'a Route, HashMap<String, String>),
+    Redirect(String),
+    File(&'a Directory),
+}
 
+/// Find the route or directory answering the request, refusing it if there is none or its
+/// access rules don't allow it
+fn resolve_target<'a>(request: &HttpRequest, routes: &'a [Route], directories: &'a [Directory]) -> Result<Target<'a>, ServerError> {
     // TODO: make a rich type mapping for finding routes, 
     // e.g. RouteMatch enum with 
         // Match(HashMap<String, String>)
@@ -120,40 +295,47 @@ pub fn handle_client(mut stream: TcpStream, routes: &[Route], directories: &[Dir
         if request.method() == route.method() {
             match match_request_to_route(&request.path(), &route.path()) {
                 RouteMatch::Match(params) => {
-                    return route.call(&mut stream, params);
+                    return Ok(Target::Route(route, params));
                 }
                 RouteMatch::Redirect(correct_path) => {
-                    return respond_redirect(&mut stream, &correct_path);
+                    return Ok(Target::Redirect(correct_path));
                 }
                 RouteMatch::Malformed(error) => {
                     //get the correct path
-                    return respond_bad_request(&mut stream, &error);
+                    return Err(ServerError::BadRequest(error));
                 }
                 RouteMatch::NoMatch => {}
             }
         }
     }
 
-    for directory in directories {
-        if request.path().starts_with(&directory.path()) {
-            if directory.allow_subdirectories || directory_is_first_level(&request.path(), &directory.path()){
-                return handle_file_case(&mut stream, &request.path());
-            } else if !directory_is_first_level(&request.path(), &directory.path()) {
-                return respond_forbidden(&mut stream, "Forbidden, Access Denied");
-            }
+    // the most specific directory containing the file decides, so "/src/" wins over "/"
+    let containing_directory = directories.iter()
+        .filter_map(|directory| directory.depth_of(&request.path()).map(|depth| (directory, depth)))
+        .min_by_key(|(_, depth)| *depth);
+
+    if let Some((directory, depth)) = containing_directory {
+        if !directory.allows_depth(depth) {
+            return Err(ServerError::Forbidden("Forbidden, Access Denied".to_string()));
+        }
+        if !directory.is_accessible(&request.path()) {
+            return Err(match directory.access_rules().denied_response() {
+                DeniedResponse::NotFound => ServerError::NotFound("Not Found".to_string()),
+                DeniedResponse::Forbidden => ServerError::Forbidden("Forbidden, Access Denied".to_string()),
+            });
         }
+        return Ok(Target::File(directory));
     }
 
-    respond_not_found(&mut stream, "Not Found")
-    
+    Err(ServerError::NotFound("Not Found".to_string()))
 }
 
 /// Figure out the malformation of a request to give a more specific error
 /// first, match the path of the request to the path of the route to find the differences
-fn malformatted_request_path(request_parts: &Vec<&str>, route_parts: &Vec<&str>) -> RouteMatch {
-    for i in 0..route_parts.len() {
+fn malformatted_request_path(request_parts: &[&str], route_parts: &[&str]) -> RouteMatch {
+    for (i, route_part) in route_parts.iter().enumerate() {
         
-        if route_parts[i].starts_with(':') {
+        if route_part.starts_with(':') {
             //parameter
             if request_parts.get(i).is_none() {
                 return RouteMatch::Malformed("Missing parameter".to_string());
@@ -170,6 +352,7 @@ fn malformatted_request_path(request_parts: &Vec<&str>, route_parts: &Vec<&str>)
 #[derive(Debug, PartialEq)]
 enum RouteMatch {
     Match(HashMap<String, String>),
+    #[allow(dead_code)]
     Redirect(String),
     Malformed(String),
     NoMatch
@@ -206,6 +389,11 @@ fn match_request_to_route(request_path: &str, route_path: &str) -> RouteMatch {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::io::Write;
+    use std::net::{TcpListener, TcpStream};
+    use std::thread;
+    use crate::http_builder::{HttpRequestLine, HttpStatus};
+    use crate::server::responses::ok::respond_ok_with_body_bytes_and_type;
 
     #[test]
     fn test_basic_match_request_to_route() {
@@ -247,4 +435,92 @@ mod tests {
         assert_eq!(match_request_to_route("/your//", "/your/:id"), RouteMatch::Malformed("Missing parameter".to_string()));
         assert_eq!(match_request_to_route("/your//and/name", "/your/:id/and/:name"), RouteMatch::Match(HashMap::from([("id".to_string(), "".to_string()), ("name".to_string(), "name".to_string())])));
     }
+
+    fn echo_body(stream: &mut ClientStream, request: &HttpRequest, _params: HashMap<String, String>) -> Result<(), ServerError> {
+        Ok(respond_ok_with_body_bytes_and_type(stream, request, request.body(), MimeType::PLAIN_TEXT)?)
+    }
+
+    fn require_token(request: &HttpRequest) -> Result<(), ServerError> {
+        match request.get_header("X-Token") {
+            Some(_) => Ok(()),
+            None => Err(ServerError::handler(HttpStatus::Unauthorized, "Missing token")),
+        }
+    }
+
+    /// Serve one connection with `routes`, sending `head` and then, once the server answers
+    /// `100 Continue`, `body`, returning everything the server wrote
+    fn exchange(routes: Vec<Route>, config: ConnectionConfig, head: &str, body: &str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let _ = handle_client(&mut ClientStream::new(stream), &routes, &[], &config, &mut None);
+        });
+
+        let mut client = TcpStream::connect(address).unwrap();
+        client.write_all(head.as_bytes()).unwrap();
+        let mut response = Vec::new();
+        let mut buffer = [0; 1024];
+        let size = client.read(&mut buffer).unwrap();
+        response.extend_from_slice(&buffer[..size]);
+        if response.starts_with(b"HTTP/1.1 100 Continue\r\n\r\n") {
+            client.write_all(body.as_bytes()).unwrap();
+        }
+        client.read_to_end(&mut response).unwrap();
+        server.join().unwrap();
+        String::from_utf8(response).unwrap()
+    }
+
+    fn upload_head(length: usize, extra: &str) -> String {
+        format!("POST /upload HTTP/1.1\r\nHost: localhost\r\nExpect: 100-continue\r\nContent-Length: {}\r\n{}\r\n", length, extra)
+    }
+
+    #[test]
+    fn test_expects_continue() {
+        let request = |version: HttpVersion, expect: Option<&str>| {
+            let mut request = HttpRequest::new(HttpRequestLine::new(HttpMethod::GET, "/").with_version(version));
+            if let Some(expect) = expect {
+                request.add_header(HttpHeader::Custom("Expect".to_string(), expect.to_string()));
+            }
+            request
+        };
+        assert!(!expects_continue(&request(HttpVersion::Http11, None)).unwrap());
+        assert!(expects_continue(&request(HttpVersion::Http11, Some("100-Continue"))).unwrap());
+        assert_eq!(expects_continue(&request(HttpVersion::Http11, Some("100-continue, fast"))).unwrap_err().status(), ExpectationFailed);
+        // HTTP/1.0 clients are never waiting for an interim response
+        assert!(!expects_continue(&request(HttpVersion::Http10, Some("100-continue"))).unwrap());
+    }
+
+    #[test]
+    fn test_continue_delivers_body_to_handler() {
+        let routes = || vec![Route::new("/upload", HttpMethod::POST, echo_body).with_guard(require_token)];
+
+        let response = exchange(routes(), ConnectionConfig::default(), &upload_head(11, "X-Token: yes\r\n"), "hello world");
+        assert!(response.starts_with("HTTP/1.1 100 Continue\r\n\r\nHTTP/1.1 200 OK\r\n"));
+        assert!(response.ends_with("\r\n\r\nhello world"));
+
+        // refused before the client is told to send the body
+        let response = exchange(routes(), ConnectionConfig::default(), &upload_head(11, ""), "hello world");
+        assert!(response.starts_with("HTTP/1.1 401 Unauthorized\r\n"));
+
+        let config = ConnectionConfig { limits: ParserLimits { max_body_size: 4, ..ParserLimits::default() }, ..ConnectionConfig::default() };
+        let response = exchange(routes(), config, &upload_head(11, "X-Token: yes\r\n"), "hello world");
+        assert!(response.starts_with("HTTP/1.1 413 Content Too Large\r\n"));
+    }
+
+    #[test]
+    fn test_errors_answer_with_connection_config() {
+        let head = "GET /missing HTTP/1.1\r\nHost: localhost\r\nAccept: application/json\r\n\r\n";
+        let response = exchange(Vec::new(), ConnectionConfig::default(), head, "");
+        assert!(response.starts_with("HTTP/1.1 404 Not Found\r\n"));
+        assert!(response.ends_with("{\"status\":404,\"error\":\"Not Found\",\"message\":\"Not Found\"}"));
+
+        let config = ConnectionConfig {
+            error_pages: Arc::new(ErrorPages::new().with_handler(HttpStatus::NotFound, |stream, context, status, _| {
+                HttpResponse::with_context(status.clone(), context).text("custom").write_to(stream)
+            })),
+            ..ConnectionConfig::default()
+        };
+        assert!(exchange(Vec::new(), config, head, "").ends_with("\r\n\r\ncustom"));
+    }
 }
\ No newline at end of file
@@ -1,6 +1,15 @@
 pub mod handle_client;
 pub mod responses;
 pub mod util;
+#[allow(clippy::module_inception)]
 pub mod server;
 pub mod routes;
-pub mod directories;
\ No newline at end of file
+pub mod directories;
+pub mod cache_policy;
+pub mod access_rules;
+pub mod error_pages;
+pub mod error;
+pub mod negotiation;
+pub mod sse;
+pub mod websocket;
+pub mod client_stream;
\ No newline at end of file
@@ -0,0 +1,51 @@
+use crate::http_builder::negotiation::Preferences;
+use crate::server::error::ServerError;
+use crate::server::util::mime_types::MimeType;
+
+/// The best of the types a handler can respond with, answering `406 Not Acceptable` if none are
+/// acceptable, offers are in the server's order of preference
+///
+/// e.g. `let content_type = negotiate_media_type(&request.preferences(), &[MimeType::HTML, MimeType::JSON])?;`
+pub fn negotiate_media_type(preferences: &Preferences, offers: &[MimeType]) -> Result<MimeType, ServerError> {
+    preferences.media_type(offers).cloned()
+        .ok_or_else(|| not_acceptable("type", offers.iter().map(|offer| offer.to_string())))
+}
+
+pub fn negotiate_language(preferences: &Preferences, offers: &[&str]) -> Result<String, ServerError> {
+    preferences.language(offers).map(str::to_string)
+        .ok_or_else(|| not_acceptable("language", offers.iter().map(|offer| offer.to_string())))
+}
+
+pub fn negotiate_charset(preferences: &Preferences, offers: &[&str]) -> Result<String, ServerError> {
+    preferences.charset(offers).map(str::to_string)
+        .ok_or_else(|| not_acceptable("charset", offers.iter().map(|offer| offer.to_string())))
+}
+
+pub fn negotiate_encoding(preferences: &Preferences, offers: &[&str]) -> Result<String, ServerError> {
+    preferences.encoding(offers).map(str::to_string)
+        .ok_or_else(|| not_acceptable("encoding", offers.iter().map(|offer| offer.to_string())))
+}
+
+/// Lists what is available, so the client can see what it could have asked for
+fn not_acceptable(what: &str, offers: impl Iterator<Item = String>) -> ServerError {
+    ServerError::NotAcceptable(format!("No acceptable {}, available: {}", what, offers.collect::<Vec<String>>().join(", ")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http_builder::negotiation::parse_accept;
+    use crate::http_builder::HttpStatus;
+
+    #[test]
+    fn test_negotiate_media_type() {
+        let preferences = Preferences { accept: parse_accept("application/json, text/*;q=0.5"), ..Preferences::default() };
+        assert_eq!(negotiate_media_type(&preferences, &[MimeType::HTML, MimeType::JSON]).unwrap(), MimeType::JSON);
+        assert_eq!(negotiate_media_type(&preferences, &[MimeType::PNG, MimeType::CSS]).unwrap(), MimeType::CSS);
+
+        let error = negotiate_media_type(&preferences, &[MimeType::PNG, MimeType::GIF]).unwrap_err();
+        assert_eq!(error.status(), HttpStatus::NotAcceptable);
+        assert_eq!(error.message(), "No acceptable type, available: image/png, image/gif");
+        assert_eq!(negotiate_media_type(&Preferences::default(), &[MimeType::PNG, MimeType::GIF]).unwrap(), MimeType::PNG);
+    }
+}
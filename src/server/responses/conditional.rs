@@ -0,0 +1,15 @@
+use std::io;
+
+use crate::server::client_stream::ClientStream;
+use crate::http_builder::{HttpHeader, HttpRequest, HttpResponse, HttpStatus::NotModified};
+
+/// Responds 304 Not Modified
+///
+/// there is no body, `headers` should repeat the validators the full response would have carried
+pub fn respond_not_modified(stream: &mut ClientStream, request: &HttpRequest, headers: Vec<HttpHeader>) -> io::Result<()> {
+    let mut response = HttpResponse::for_request(NotModified, request);
+    for header in headers {
+        response.add_header(header);
+    }
+    response.write_to(stream)
+}
@@ -1,36 +1,214 @@
-use std::{io::{self, Write}, net::TcpStream};
+use std::sync::Arc;
+use std::{fs, io};
 
-use crate::http_builder::{write_http_response_header, HttpStatus, HttpStatus::{BadRequest, Forbidden, NotFound, InternalServerError}};
-use crate::server::util::mime_types::MimeType::PlainText;
+use crate::server::client_stream::ClientStream;
+use crate::html_builder::Page;
+use crate::http_builder::{HttpResponse, HttpStatus, ResponseContext, HttpStatus::{BadRequest, Forbidden, NotFound, InternalServerError}};
+use crate::http_builder::negotiation::Preferences;
+use crate::server::error_pages::{ErrorPage, ErrorPages};
+use crate::server::util::mime_types::{MimeType, MimeTypes};
+use crate::server::util::uri::get_file_extension;
 
+/// What an error response needs to know about the server and the request it answers,
+/// the default has no custom pages and answers in plain text
+#[derive(Debug, Clone, Default)]
+pub struct ErrorContext {
+    /// What the error response is built with, the request's once it has been parsed
+    pub response: ResponseContext,
+    /// What the request accepts, picking the format of the error body
+    pub preferences: Preferences,
+    pub pages: Arc<ErrorPages>,
+    /// Types custom error page files are sent as
+    pub mime_types: Arc<MimeTypes>,
+}
+
+/// Responds with the built in error for the status, as plain text
+pub fn respond_error_with_body_and_status(stream: &mut ClientStream, body: &str, status: HttpStatus) -> io::Result<()> {
+    respond_error_with_context(stream, &ErrorContext::default(), body, status)
+}
 
 /// Responds with an error determined by status
-/// 
-/// helper function to write an error response with a body
-pub fn respond_error_with_body_and_status(stream: &mut TcpStream, body: &str, status: HttpStatus) -> io::Result<()> {
-    let header = write_http_response_header(status, Some(PlainText), None);
-    let response = format!("{}Error - {}\r\n", header, body);
-    stream.write_all(response.as_bytes())?;
-    stream.flush()?;
-    Ok(())
+///
+/// sends the custom page registered for the status if there is one, otherwise
+/// the message as HTML, JSON or plain text, whichever the request's Accept prefers
+pub fn respond_error_with_context(stream: &mut ClientStream, context: &ErrorContext, body: &str, status: HttpStatus) -> io::Result<()> {
+    match context.pages.get(&status) {
+        // a handler that responds with an error itself gets the built in response
+        Some(ErrorPage::Handler(handler)) => return handler(stream, &context.response, &status, body),
+        Some(ErrorPage::File(path)) => {
+            // a missing page falls back to the built in response rather than hiding the error
+            if let Ok(contents) = fs::read(path) {
+                let content_type = context.mime_types.lookup(&get_file_extension(&path.to_string_lossy()));
+                return HttpResponse::with_context(status, &context.response).bytes(contents, content_type).write_to(stream);
+            }
+        }
+        None => {}
+    }
+
+    let response = HttpResponse::with_context(status.clone(), &context.response);
+    let format = preferred_error_format(&context.preferences);
+    if format == MimeType::HTML {
+        response.html(error_page(&status, body))
+    } else if format == MimeType::JSON {
+        response.json(error_json(&status, body))
+    } else {
+        response.text(format!("Error - {}\r\n", body))
+    }
+    .write_to(stream)
+}
+
+/// Pick HTML, JSON or plain text for an error body from the request's Accept header
+///
+/// ties go to the earlier format, and plain text is used when there's no header or nothing acceptable,
+/// an error response is better than a 406
+fn preferred_error_format(preferences: &Preferences) -> MimeType {
+    if preferences.accept.is_none() {
+        return MimeType::PLAIN_TEXT;
+    }
+    preferences.media_type(&[MimeType::HTML, MimeType::JSON, MimeType::PLAIN_TEXT])
+        .cloned()
+        .unwrap_or(MimeType::PLAIN_TEXT)
+}
+
+fn error_page(status: &HttpStatus, message: &str) -> String {
+    let mut page = Page::new();
+    let title = escape_html(&status.to_string());
+    page.add_title(&title);
+    page.add_heading(1, &title);
+    page.add_paragraph(&escape_html(message));
+    page.to_string()
+}
+
+fn error_json(status: &HttpStatus, message: &str) -> String {
+    format!(
+        "{{\"status\":{},\"error\":\"{}\",\"message\":\"{}\"}}",
+        status.code(),
+        escape_json(status.reason_phrase()),
+        escape_json(message)
+    )
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn escape_json(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
 }
 
 /// 400 Bad Request
-pub fn respond_bad_request(stream: &mut TcpStream, err: &str) -> io::Result<()> {
+pub fn respond_bad_request(stream: &mut ClientStream, err: &str) -> io::Result<()> {
     respond_error_with_body_and_status(stream, err, BadRequest)
 }
 
 /// 403 Forbidden
-pub fn respond_forbidden(stream: &mut TcpStream, err: &str) -> io::Result<()> {
+pub fn respond_forbidden(stream: &mut ClientStream, err: &str) -> io::Result<()> {
     respond_error_with_body_and_status(stream, err, Forbidden)
 }
 
 /// responds 404 Not Found
-pub fn respond_not_found(stream: &mut TcpStream, err: &str) -> io::Result<()> {
+pub fn respond_not_found(stream: &mut ClientStream, err: &str) -> io::Result<()> {
     respond_error_with_body_and_status(stream, err, NotFound)
 }
 
 /// Responds 500 Internal Server Error
-pub fn respond_internal_server_error(stream: &mut TcpStream, err: &str) -> io::Result<()> {
+pub fn respond_internal_server_error(stream: &mut ClientStream, err: &str) -> io::Result<()> {
     respond_error_with_body_and_status(stream, err, InternalServerError)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_preferred_error_format() {
+        let preferred_error_format = |accept: Option<&str>| preferred_error_format(&Preferences {
+            accept: accept.and_then(crate::http_builder::negotiation::parse_accept),
+            ..Preferences::default()
+        });
+        assert_eq!(preferred_error_format(None), MimeType::PLAIN_TEXT);
+        assert_eq!(preferred_error_format(Some("text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8")), MimeType::HTML);
+        assert_eq!(preferred_error_format(Some("application/json")), MimeType::JSON);
+        assert_eq!(preferred_error_format(Some("text/*;q=0.5, application/json;q=0.4")), MimeType::HTML);
+        assert_eq!(preferred_error_format(Some("text/html;q=0.1, application/json;q=0.9")), MimeType::JSON);
+        assert_eq!(preferred_error_format(Some("*/*")), MimeType::HTML);
+        assert_eq!(preferred_error_format(Some("text/*, text/html;q=0")), MimeType::PLAIN_TEXT);
+        assert_eq!(preferred_error_format(Some("image/png")), MimeType::PLAIN_TEXT);
+    }
+
+    #[test]
+    fn test_error_bodies() {
+        assert_eq!(
+            error_json(&NotFound, "No \"such\" file\n"),
+            "{\"status\":404,\"error\":\"Not Found\",\"message\":\"No \\\"such\\\" file\\n\"}"
+        );
+        let page = error_page(&BadRequest, "<script>alert(1)</script>");
+        assert!(page.contains("400 Bad Request"));
+        assert!(page.contains("&lt;script&gt;alert(1)&lt;/script&gt;"));
+        assert!(!page.contains("<script>"));
+    }
+
+    fn respond_over_socket(status: HttpStatus, pages: ErrorPages) -> String {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut client = std::net::TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (stream, _) = listener.accept().unwrap();
+        let mut stream = ClientStream::new(stream);
+
+        let context = ErrorContext { pages: Arc::new(pages), ..ErrorContext::default() };
+        respond_error_with_context(&mut stream, &context, "message", status).unwrap();
+        drop(stream);
+
+        let mut received = String::new();
+        io::Read::read_to_string(&mut client, &mut received).unwrap();
+        received
+    }
+
+    #[test]
+    fn test_custom_error_pages() {
+        let path = std::env::temp_dir().join(format!("http_server_error_page_{}.html", std::process::id()));
+        fs::write(&path, "<h1>gone</h1>").unwrap();
+        let pages = ErrorPages::new()
+            .with_file(NotFound, path.to_str().unwrap())
+            .with_file(Forbidden, "does/not/exist.html")
+            .with_handler(InternalServerError, |stream, _, status, message| {
+                assert_eq!(*status, InternalServerError);
+                //responding with another error from a handler doesn't recurse into it
+                respond_error_with_body_and_status(stream, &format!("handled {}", message), status.clone())
+            });
+
+        let response = respond_over_socket(NotFound, pages.clone());
+        assert!(response.starts_with("HTTP/1.1 404 Not Found\r\n"));
+        assert!(response.contains("Content-Type: text/html\r\nContent-Length: 13\r\n"));
+        assert!(response.ends_with("\r\n\r\n<h1>gone</h1>"));
+
+        let response = respond_over_socket(Forbidden, pages.clone());
+        assert!(response.ends_with("\r\n\r\nError - message\r\n"));
+
+        let response = respond_over_socket(InternalServerError, pages);
+        assert!(response.ends_with("\r\n\r\nError - handled message\r\n"));
+        fs::remove_file(path).unwrap();
+    }
 }
\ No newline at end of file
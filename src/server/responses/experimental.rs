@@ -1,19 +1,22 @@
 use std::collections::HashMap;
-use std::net::TcpStream;
 use std::io;
 
+use crate::server::client_stream::ClientStream;
 use crate::html_builder::Page;
+use crate::http_builder::HttpRequest;
 use crate::server::util::mime_types::MimeType;
+use crate::server::error::ServerError;
 use crate::server::util::barcode_encoding::encode_to_barcode_bitstring;
 use crate::server::util::images::bitmap::Bitmap;
+use crate::server::websocket::{Message, WebSocket};
 
-use super::ok::{respond_ok_with_body_and_type, respond_ok_with_body_bytes_and_type};
+use super::ok::{param, respond_ok_with_body_and_type, respond_ok_with_body_bytes_and_type};
 
-pub fn respond_redirect(stream: &mut TcpStream, url: &str) -> io::Result<()> {
+pub fn respond_redirect(_stream: &mut ClientStream, _url: &str) -> io::Result<()> {
     todo!("implement redirect")
 }
 
-pub fn respond_ok_webgl(stream: &mut TcpStream, params: HashMap<String, String>) -> io::Result<()> {
+pub fn respond_ok_webgl(stream: &mut ClientStream, request: &HttpRequest, _params: HashMap<String, String>) -> Result<(), ServerError> {
     
     let mut response  = Page::new();
     let title = "WebGL HTTP Server Demo Page";
@@ -21,11 +24,29 @@ pub fn respond_ok_webgl(stream: &mut TcpStream, params: HashMap<String, String>)
     response.add_heading(1, title);
     response.add_canvas(800, 600, None, true);
 
-    respond_ok_with_body_and_type(stream, response.to_string().as_str(), MimeType::Html)
+    Ok(respond_ok_with_body_and_type(stream, request, response.to_string().as_str(), MimeType::HTML)?)
 }
 
-pub fn respond_ok_barcode(stream: &mut TcpStream, params: HashMap<String, String>) -> io::Result<()> {
-    let to_encode = params.get("data").unwrap();
+/// Live data channel for the WebGL demo page, every message is answered with the next clear color
+pub fn respond_websocket_webgl(websocket: &mut WebSocket<&mut ClientStream>, _request: &HttpRequest, _params: HashMap<String, String>) -> Result<(), ServerError> {
+    let mut frame = 0u32;
+    loop {
+        match websocket.recv()? {
+            Message::Text(_) => {
+                frame = frame.wrapping_add(1);
+                // each channel follows a sine wave a third of a turn apart from the others
+                let phase = frame as f32 / 60.0;
+                let channel = |offset: f32| (phase + offset * std::f32::consts::TAU / 3.0).sin() * 0.5 + 0.5;
+                websocket.send_text(&format!("{:.3} {:.3} {:.3}", channel(0.0), channel(1.0), channel(2.0)))?;
+            }
+            Message::Close(_) => return Ok(()),
+            _ => {}
+        }
+    }
+}
+
+pub fn respond_ok_barcode(stream: &mut ClientStream, request: &HttpRequest, params: HashMap<String, String>) -> Result<(), ServerError> {
+    let to_encode = param(&params, "data")?;
     let bitstring = encode_to_barcode_bitstring(to_encode);
 
     let mut content = Vec::new();
@@ -39,5 +60,5 @@ pub fn respond_ok_barcode(stream: &mut TcpStream, params: HashMap<String, String
     let response = Bitmap::new(pixels.iter().flat_map(|p| {
         vec![p[0], p[1], p[2]]
     }).collect::<Vec<u8>>(),(bitstring.len() as u32, 20), 24);
-    respond_ok_with_body_bytes_and_type(stream, &response.write_bitmap(), MimeType::Bitmap)
+    Ok(respond_ok_with_body_bytes_and_type(stream, request, &response.write_bitmap(), MimeType::BITMAP)?)
 }
@@ -1,3 +1,5 @@
+pub mod conditional;
 pub mod error;
 pub mod experimental;
 pub mod ok;
+pub mod partial;
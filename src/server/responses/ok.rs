@@ -1,21 +1,24 @@
 use std::collections::HashMap;
-use std::net::TcpStream;
-use std::io::{self, Write};
-use crate::http_builder::write_http_response_header;
+use std::io;
+use crate::server::client_stream::ClientStream;
 use crate::html_builder::Page;
-use crate::http_builder::HttpStatus::RequestOk;
-use crate::server::util::mime_types::MimeType::{*, self};
+use crate::http_builder::{HttpRequest, HttpResponse};
+use crate::http_builder::streaming::BodyStream;
+use crate::http_builder::HttpStatus::{self, RequestOk};
+use crate::server::error::ServerError;
+use crate::server::negotiation::negotiate_media_type;
+use crate::server::util::mime_types::MimeType;
 
 /// Responds 200 OK
-pub fn respond_ok(stream: &mut TcpStream, params: HashMap<String, String>) -> io::Result<()> {
+pub fn respond_ok(stream: &mut ClientStream, request: &HttpRequest, params: HashMap<String, String>) -> Result<(), ServerError> {
     if !params.is_empty() {
-        return respond_ok_with_body_and_type(stream, "This should never not be empty, contact system admin", PlainText);
+        return Ok(respond_ok_with_body_and_type(stream, request, "This should never not be empty, contact system admin", MimeType::PLAIN_TEXT)?);
     }
 
     let mut response  = Page::new();
     let hello = "Hello, World!";
     response.add_title(hello);
-    response.add_script(JavaScript, Some("hello_world.js"), None);
+    response.add_script(MimeType::JAVASCRIPT, Some("hello_world.js"), None);
     response.add_style(None, Some("* { font-family: monospace; }"));
 
     response.add_heading(1, hello);
@@ -24,40 +27,50 @@ pub fn respond_ok(stream: &mut TcpStream, params: HashMap<String, String>) -> io
 
     response.add_hyperlink("WebGL Demo", "/webgl");
 
-    respond_ok_with_body_and_type(stream, response.to_string().as_str(), Html)
+    Ok(respond_ok_with_body_and_type(stream, request, response.to_string().as_str(), MimeType::HTML)?)
 }
 
 /// Responds 200 OK 
 /// 
 /// helper function to write a response with a body
-pub fn respond_ok_with_body_and_type(stream: &mut TcpStream, body: &str, content_type: MimeType) -> io::Result<()> {
-    let header = write_http_response_header(RequestOk, Some(content_type), Some(body.len() as u64));
-    let response = format!("{}{}", header, body);
-    stream.write_all(response.as_bytes())?;
-    stream.flush()?;
-    Ok(())
+pub fn respond_ok_with_body_and_type(stream: &mut ClientStream, request: &HttpRequest, body: &str, content_type: MimeType) -> io::Result<()> {
+    respond_ok_with_body_bytes_and_type(stream, request, body.as_bytes(), content_type)
 }
 
-pub fn respond_ok_with_body_bytes_and_type(stream: &mut TcpStream, body: &[u8], content_type: MimeType) -> io::Result<()> {
-    let header = write_http_response_header(RequestOk, Some(content_type), Some(body.len() as u64));
-    let response = vec![header.as_bytes(), body].concat();
-    stream.write_all(&response)?;
-    stream.flush()?;
-    Ok(())
+pub fn respond_ok_with_body_bytes_and_type(stream: &mut ClientStream, request: &HttpRequest, body: &[u8], content_type: MimeType) -> io::Result<()> {
+    HttpResponse::for_request(RequestOk, request)
+        .bytes(body.to_vec(), content_type)
+        .write_to(stream)
 }
 
-pub fn respond_ok_memes(stream: &mut TcpStream, params: HashMap<String, String>) -> io::Result<()> {
+/// Responds 200 OK without knowing the body's length, the handler sends it a piece at a time
+/// with `send` and ends it with `finish`
+pub fn respond_ok_streaming<'a>(stream: &'a mut ClientStream, request: &HttpRequest, content_type: MimeType) -> io::Result<BodyStream<&'a mut ClientStream>> {
+    HttpResponse::for_request(RequestOk, request).stream(content_type, stream)
+}
+
+pub fn respond_ok_memes(stream: &mut ClientStream, request: &HttpRequest, params: HashMap<String, String>) -> Result<(), ServerError> {
     let _ = params;
-    return respond_ok_with_body_and_type(stream, "\"memes\" [\n\t\"meme\"\n]", Json);
+    let content_type = negotiate_media_type(&request.preferences(), &[MimeType::JSON])?;
+    Ok(respond_ok_with_body_and_type(stream, request, "\"memes\" [\n\t\"meme\"\n]", content_type)?)
+}
+
+pub fn respond_ok_id(stream: &mut ClientStream, request: &HttpRequest, params: HashMap<String, String>) -> Result<(), ServerError> {
+    let id = param(&params, "id")?;
+    Ok(respond_ok_with_body_and_type(stream, request, format!("Your id is: {}", id).as_str(), MimeType::PLAIN_TEXT)?)
 }
 
-pub fn respond_ok_id(stream: &mut TcpStream, params: HashMap<String, String>) -> io::Result<()> {
-    let id = params.get("id").unwrap();
-    return respond_ok_with_body_and_type(stream, format!("Your id is: {}", id).as_str(), PlainText);
+pub fn respond_ok_abxy(stream: &mut ClientStream, request: &HttpRequest, params: HashMap<String, String>) -> Result<(), ServerError> {
+    let b = param(&params, "b")?;
+    let y = param(&params, "y")?;
+    Ok(respond_ok_with_body_and_type(stream, request, format!("{} {}", b, y).as_str(), MimeType::PLAIN_TEXT)?)
 }
 
-pub fn respond_ok_abxy(stream: &mut TcpStream, params: HashMap<String, String>) -> io::Result<()> {
-    let b = params.get("b").unwrap();
-    let y = params.get("y").unwrap();
-    return respond_ok_with_body_and_type(stream, format!("{} {}", b, y).as_str(), PlainText)
+/// Look up a route parameter, a missing one means the route and handler disagree, which is the
+/// server's fault rather than the client's, so the name is only logged
+pub fn param<'a>(params: &'a HashMap<String, String>, name: &str) -> Result<&'a String, ServerError> {
+    params.get(name).ok_or_else(|| {
+        eprintln!("Error: missing route parameter: {}", name);
+        ServerError::handler(HttpStatus::InternalServerError, "Something went wrong! Contact the server administrator.")
+    })
 }
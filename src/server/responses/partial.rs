@@ -0,0 +1,86 @@
+use std::fs::File;
+use std::io::{self, Write};
+
+use crate::server::client_stream::ClientStream;
+use crate::http_builder::{HttpHeader, HttpRequest, HttpResponse, HttpStatus::{self, PartialContent, RangeNotSatisfiable, RequestOk}};
+use crate::server::util::mime_types::MimeType;
+use crate::server::util::range::{multipart_boundary, ByteRange};
+
+fn accept_ranges() -> HttpHeader {
+    HttpHeader::Custom("Accept-Ranges".to_string(), "bytes".to_string())
+}
+
+/// The head of a response whose body is sent straight from the file, so it is never compressed
+fn file_response_head(status: HttpStatus, request: &HttpRequest, content_type: MimeType, length: u64, headers: Vec<HttpHeader>) -> HttpResponse {
+    let mut response = HttpResponse::for_request(status, request);
+    response.add_header(HttpHeader::ContentType(content_type));
+    response.add_header(HttpHeader::ContentLength(length));
+    for header in headers {
+        response.add_header(header);
+    }
+    response
+}
+
+/// Responds 200 OK with the whole file, advertising that byte ranges are supported
+///
+/// `headers` carries anything describing the file itself, such as its validators
+pub fn respond_ok_with_ranges_accepted(stream: &mut ClientStream, request: &HttpRequest, file: &mut File, length: u64, content_type: MimeType, mut headers: Vec<HttpHeader>) -> io::Result<()> {
+    headers.push(accept_ranges());
+    file_response_head(RequestOk, request, content_type, length, headers).write_head_to(stream)?;
+    stream.send_file(file, 0, length)?;
+    stream.flush()
+}
+
+/// Responds 206 Partial Content with a single range of the file
+pub fn respond_partial_content(stream: &mut ClientStream, request: &HttpRequest, file: &mut File, content_type: MimeType, range: &ByteRange, total_length: u64, mut headers: Vec<HttpHeader>) -> io::Result<()> {
+    headers.insert(0, HttpHeader::Custom("Content-Range".to_string(), range.content_range(total_length)));
+    headers.push(accept_ranges());
+    file_response_head(PartialContent, request, content_type, range.len(), headers).write_head_to(stream)?;
+    stream.send_file(file, range.start, range.len())?;
+    stream.flush()
+}
+
+/// Responds 206 Partial Content with several ranges of the file as a `multipart/byteranges` body
+///
+/// each part carries its own Content-Type and Content-Range
+pub fn respond_multipart_byteranges(stream: &mut ClientStream, request: &HttpRequest, file: &mut File, ranges: &[ByteRange], content_type: MimeType, total_length: u64, headers: Vec<HttpHeader>) -> io::Result<()> {
+    let boundary = multipart_boundary();
+
+    let part_headers: Vec<String> = ranges.iter()
+        .map(|range| format!(
+            "\r\n--{}\r\nContent-Type: {}\r\nContent-Range: {}\r\n\r\n",
+            boundary, content_type, range.content_range(total_length)
+        ))
+        .collect();
+    let closing = format!("\r\n--{}--\r\n", boundary);
+
+    // the length has to be known up front since the parts are streamed straight from the file
+    let content_length = part_headers.iter().map(|part| part.len() as u64).sum::<u64>()
+        + ranges.iter().map(ByteRange::len).sum::<u64>()
+        + closing.len() as u64;
+
+    let mut response = HttpResponse::for_request(PartialContent, request);
+    response.add_header(HttpHeader::ContentType(MimeType::from_static("multipart", "byteranges").with_parameter("boundary", &boundary)));
+    response.add_header(HttpHeader::ContentLength(content_length));
+    for header in headers {
+        response.add_header(header);
+    }
+    response.add_header(accept_ranges());
+
+    response.write_head_to(stream)?;
+    for (range, part_header) in ranges.iter().zip(part_headers) {
+        stream.write_all(part_header.as_bytes())?;
+        stream.send_file(file, range.start, range.len())?;
+    }
+    stream.write_all(closing.as_bytes())?;
+    stream.flush()
+}
+
+/// Responds 416 Range Not Satisfiable, telling the client the actual length of the file
+pub fn respond_range_not_satisfiable(stream: &mut ClientStream, request: &HttpRequest, total_length: u64) -> io::Result<()> {
+    HttpResponse::for_request(RangeNotSatisfiable, request)
+        .header(HttpHeader::Custom("Content-Range".to_string(), format!("bytes */{}", total_length)))
+        .header(accept_ranges())
+        .text("Error - Range Not Satisfiable\r\n")
+        .write_to(stream)
+}
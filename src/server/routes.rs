@@ -1,10 +1,26 @@
 use std::collections::HashMap;
-use std::net::TcpStream;
-use std::io;
 
-use crate::http_builder::HttpMethod;
+use crate::server::client_stream::ClientStream;
+use crate::http_builder::{HttpMethod, HttpRequest};
+use crate::server::error::ServerError;
+use crate::server::websocket::{accept_upgrade, CloseCode, WebSocket};
 
-type Handler = fn(&mut TcpStream, HashMap<String, String>) -> io::Result<()>;
+/// A route's handler, given the request it answers, errors it returns are answered with their matching status
+pub type Handler = fn(&mut ClientStream, &HttpRequest, HashMap<String, String>) -> Result<(), ServerError>;
+
+/// A WebSocket route's handler, run with the connection once the handshake has completed,
+/// a connection it returns from without closing is closed for it
+pub type WebSocketHandler = fn(&mut WebSocket<&mut ClientStream>, &HttpRequest, HashMap<String, String>) -> Result<(), ServerError>;
+
+/// A check on a request's head run before its body is read, an error refuses the request
+/// without the client sending the body, e.g. after `Expect: 100-continue`
+pub type Guard = fn(&HttpRequest) -> Result<(), ServerError>;
+
+#[derive(Debug, Clone, Copy)]
+enum RouteHandler {
+    Http(Handler),
+    WebSocket(WebSocketHandler),
+}
 
 /// A route that can be matched against a request
 /// 
@@ -13,11 +29,21 @@ type Handler = fn(&mut TcpStream, HashMap<String, String>) -> io::Result<()>;
 pub struct Route {
     path: String,
     method: HttpMethod,
-    handler: Handler,
+    handler: RouteHandler,
+    guard: Option<Guard>,
 }
 
 impl Route {
     pub fn new(path: &str, method: HttpMethod, handler: Handler) -> Self {
+        Self::with_handler(path, method, RouteHandler::Http(handler))
+    }
+
+    /// A route upgrading GET requests to WebSocket connections, plain requests get `426 Upgrade Required`
+    pub fn websocket(path: &str, handler: WebSocketHandler) -> Self {
+        Self::with_handler(path, HttpMethod::GET, RouteHandler::WebSocket(handler))
+    }
+
+    fn with_handler(path: &str, method: HttpMethod, handler: RouteHandler) -> Self {
         //TODO: check if path and method are valid otherwise return an error (non-empty path and valid method)
         if path.is_empty() {
             panic!("Invalid route: empty path");
@@ -26,13 +52,38 @@ impl Route {
             path: path.to_string(),
             method,
             handler,
+            guard: None,
         }
     }
 
-    pub fn call(&self, stream: &mut TcpStream, params: HashMap<String, String>) -> io::Result<()> {
-        (self.handler)(stream, params)
+    /// Refuse requests failing `guard` before their body is read, e.g. uploads without credentials
+    pub fn with_guard(mut self, guard: Guard) -> Self {
+        self.guard = Some(guard);
+        self
+    }
+
+    /// Run the guard, if any, against a request's head
+    pub fn check(&self, request: &HttpRequest) -> Result<(), ServerError> {
+        self.guard.map_or(Ok(()), |guard| guard(request))
     }
 
+    pub fn call(&self, stream: &mut ClientStream, request: &HttpRequest, params: HashMap<String, String>) -> Result<(), ServerError> {
+        match self.handler {
+            RouteHandler::Http(handler) => handler(stream, request, params),
+            RouteHandler::WebSocket(handler) => match accept_upgrade(stream, request)? {
+                Some(mut websocket) => {
+                    let result = handler(&mut websocket, request, params);
+                    let code = if result.is_ok() { CloseCode::Normal } else { CloseCode::InternalError };
+                    // the client may already have gone, which leaves no one to tell
+                    let _ = websocket.close_if_open(code);
+                    result
+                }
+                None => Ok(()),
+            },
+        }
+    }
+
+
     pub fn path(&self) -> String {
         self.path.clone()
     }
@@ -40,4 +91,51 @@ impl Route {
     pub fn method(&self) -> HttpMethod {
         self.method.clone()
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+    use std::net::{TcpListener, TcpStream};
+    use crate::http_builder::{HttpHeader, HttpRequestLine, HttpStatus};
+    use crate::server::websocket::{Frame, Opcode};
+
+    fn returns_early(_websocket: &mut WebSocket<&mut ClientStream>, _request: &HttpRequest, _params: HashMap<String, String>) -> Result<(), ServerError> {
+        Ok(())
+    }
+
+    fn fails(_websocket: &mut WebSocket<&mut ClientStream>, _request: &HttpRequest, _params: HashMap<String, String>) -> Result<(), ServerError> {
+        Err(ServerError::handler(HttpStatus::InternalServerError, "gave up"))
+    }
+
+    /// Runs a WebSocket route for a client completing the handshake, returning the code of the
+    /// close frame the client receives after the 101
+    fn close_code_after(handler: WebSocketHandler) -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (stream, _) = listener.accept().unwrap();
+        let mut stream = ClientStream::new(stream);
+
+        let mut request = HttpRequest::new(HttpRequestLine::new(HttpMethod::GET, "/live"));
+        for (name, value) in [("Upgrade", "websocket"), ("Connection", "Upgrade"), ("Sec-WebSocket-Version", "13"), ("Sec-WebSocket-Key", "dGhlIHNhbXBsZSBub25jZQ==")] {
+            request.add_header(HttpHeader::Custom(name.to_string(), value.to_string()));
+        }
+        let _ = Route::websocket("/live", handler).call(&mut stream, &request, HashMap::new());
+        drop(stream);
+
+        let mut received = Vec::new();
+        client.read_to_end(&mut received).unwrap();
+        assert!(received.starts_with(b"HTTP/1.1 101 Switching Protocols\r\n"));
+        let head_length = received.windows(4).position(|window| window == b"\r\n\r\n").unwrap() + 4;
+        let frame = Frame::read_from(&mut &received[head_length..], usize::MAX).unwrap();
+        assert_eq!(frame.opcode, Opcode::Close);
+        u16::from_be_bytes([frame.payload[0], frame.payload[1]])
+    }
+
+    #[test]
+    fn test_websocket_closed_after_handler() {
+        assert_eq!(close_code_after(returns_early), CloseCode::Normal.code());
+        assert_eq!(close_code_after(fails), CloseCode::InternalError.code());
+    }
 }
\ No newline at end of file
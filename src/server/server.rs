@@ -1,14 +1,33 @@
-use std::net::TcpListener;
+use std::any::Any;
+use std::net::{TcpListener, TcpStream};
+use std::panic::{self, AssertUnwindSafe};
 use std::sync::Arc;
 use std::thread;
 
-use crate::server::handle_client::handle_client;
+use crate::http_builder::{HttpHeader, ResponseContext};
+use crate::http_builder::HttpStatus;
+use crate::http_builder::parser::ParserLimits;
+use crate::server::client_stream::ClientStream;
+use crate::server::error_pages::{ErrorPage, ErrorPages};
+use crate::server::handle_client::{handle_client, ConnectionConfig};
+use crate::server::responses::error::respond_error_with_context;
 use crate::server::routes::Route;
+use crate::server::util::mime_types::{MimeType, MimeTypes};
 use crate::server::directories::Directory;
+use crate::http_builder::compression::CompressionPolicy;
+
+/// `Server` header sent unless configured otherwise
+const DEFAULT_SERVER_HEADER: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
 
 pub struct Server {
     routes: Arc<Vec<Route>>,
     directories: Arc<Vec<Directory>>,
+    server_header: Option<String>,
+    default_headers: Vec<HttpHeader>,
+    error_pages: Arc<ErrorPages>,
+    mime_types: Arc<MimeTypes>,
+    compression: Option<CompressionPolicy>,
+    limits: ParserLimits,
 }
 
 impl Server {
@@ -18,14 +37,13 @@ impl Server {
         let mut filtered_routes = Vec::new();
 
         'route_loop: for route in routes {
-            if let Some(last_segment) = route.path().split('/').last() {
-                if last_segment.contains('.') && !last_segment.starts_with('.') {
-                    // Check if the route is in an allowed directory
-                    for dir in &directories {
-                        if route.path().starts_with(&dir.path()) {
-                            // This is a valid file path in an allowed directory, skip this route
-                            continue 'route_loop;
-                        }
+            if let Some(last_segment) = route.path().split('/').next_back()
+                && last_segment.contains('.') && !last_segment.starts_with('.') {
+                // Check if the route is in an allowed directory
+                for dir in &directories {
+                    if route.path().starts_with(&dir.path()) {
+                        // This is a valid file path in an allowed directory, skip this route
+                        continue 'route_loop;
                     }
                 }
             }
@@ -33,23 +51,98 @@ impl Server {
             filtered_routes.push(route);
         }
     
-        Self { routes: Arc::new(filtered_routes), directories: Arc::new(directories) }
+        Self {
+            routes: Arc::new(filtered_routes),
+            directories: Arc::new(directories),
+            server_header: Some(DEFAULT_SERVER_HEADER.to_string()),
+            default_headers: Vec::new(),
+            error_pages: Arc::new(ErrorPages::new()),
+            mime_types: Arc::new(MimeTypes::new()),
+            compression: None,
+            limits: ParserLimits::default(),
+        }
+
+    }
+
+    /// Identify the server as `name` in the `Server` header
+    pub fn with_server_header(mut self, name: &str) -> Self {
+        self.server_header = Some(name.to_string());
+        self
+    }
+
+    /// Don't send a `Server` header
+    pub fn without_server_header(mut self) -> Self {
+        self.server_header = None;
+        self
+    }
+
+    /// Send a header with every response, unless the response sets one with the same name itself
+    pub fn with_default_header(mut self, header: HttpHeader) -> Self {
+        self.default_headers.push(header);
+        self
+    }
+
+    /// Respond to this error status with a custom page instead of the built in one
+    pub fn with_error_page(mut self, status: HttpStatus, page: ErrorPage) -> Self {
+        let pages = (*self.error_pages).clone();
+        self.error_pages = Arc::new(pages.with_page(status, page));
+        self
+    }
+
+    /// Serve files with this extension as `mime_type`, overriding the built in table
+    pub fn with_mime_type(mut self, extension: &str, mime_type: MimeType) -> Self {
+        let mime_types = (*self.mime_types).clone();
+        self.mime_types = Arc::new(mime_types.with_type(extension, mime_type));
+        self
+    }
+
+    /// Compress responses on the fly for clients that accept gzip or deflate, off by default
+    pub fn with_compression(mut self, policy: CompressionPolicy) -> Self {
+        self.compression = Some(policy);
+        self
+    }
 
+    /// Refuse request bodies over `bytes` with `413 Content Too Large`, 8 MiB by default
+    pub fn with_max_body_size(mut self, bytes: u64) -> Self {
+        self.limits.max_body_size = bytes;
+        self
+    }
+
+    /// The context every connection's responses start from
+    fn response_context(&self) -> ResponseContext {
+        let mut default_headers = Vec::new();
+        if let Some(server_header) = &self.server_header {
+            default_headers.push(HttpHeader::Custom("Server".to_string(), server_header.clone()));
+        }
+        default_headers.extend(self.default_headers.iter().cloned());
+        ResponseContext {
+            send_date: true,
+            default_headers,
+            compression: self.compression.clone(),
+            ..ResponseContext::default()
+        }
     }
 
     pub fn run(&self) {
         let address = "127.0.0.1:8080";
         let listener = TcpListener::bind(address).unwrap();
         println!("Listening on http://{}", address);
-        
+        let config = ConnectionConfig {
+            limits: self.limits,
+            response_context: self.response_context(),
+            mime_types: self.mime_types.clone(),
+            error_pages: self.error_pages.clone(),
+        };
+
         for stream in listener.incoming() {
             match stream {
                 Ok(stream) => {
                     let rs = self.routes.clone();
                     let ds = self.directories.clone();
+                    let config = config.clone();
 
                     thread::spawn(move || {
-                        handle_client(stream, &rs, &ds).unwrap();
+                        serve_connection(stream, &rs, &ds, &config);
                     });
                 }
                 Err(e) => {
@@ -59,4 +152,42 @@ impl Server {
         }
     }
 
+}
+
+/// Handle a connection, containing any panic in a handler to this one request
+///
+/// if nothing was written before the panic the client still gets a 500, either way
+/// the error is logged and the thread finishes normally
+fn serve_connection(stream: TcpStream, routes: &[Route], directories: &[Directory], config: &ConnectionConfig) {
+    let peer = stream.peer_addr().map_or("unknown peer".to_string(), |address| address.to_string());
+    let mut stream = ClientStream::new(stream);
+
+    let mut request = None;
+    let result = panic::catch_unwind(AssertUnwindSafe(|| handle_client(&mut stream, routes, directories, config, &mut request)));
+    let description = request.as_ref()
+        .map_or("unparsed request".to_string(), |request| format!("{} {} {}", request.method(), request.path(), request.version()));
+    match result {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => eprintln!("Error: {} from {}: {}", description, peer, e),
+        Err(payload) => {
+            eprintln!("Error: handler panicked on {} from {}: {}", description, peer, panic_message(payload.as_ref()));
+            if stream.response_started() {
+                return;
+            }
+            let context = config.error_context(request.as_ref());
+            if let Err(e) = respond_error_with_context(&mut stream, &context, "Something went wrong! Contact the server administrator.", HttpStatus::InternalServerError) {
+                eprintln!("Error: could not send 500 to {}: {}", peer, e);
+            }
+        }
+    }
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message
+    } else {
+        "unknown panic"
+    }
 }
\ No newline at end of file
@@ -0,0 +1,348 @@
+use std::collections::VecDeque;
+use std::fmt::{self, Formatter};
+use std::io::{self, Write};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::server::client_stream::ClientStream;
+use crate::http_builder::streaming::BodyStream;
+use crate::http_builder::{HttpHeader, HttpRequest, HttpResponse, HttpStatus::RequestOk};
+use crate::server::util::mime_types::MimeType;
+
+pub const EVENT_STREAM: MimeType = MimeType::from_static("text", "event-stream");
+
+/// How long a quiet stream waits before sending a comment, so proxies don't time the connection out
+pub const DEFAULT_KEEP_ALIVE: Duration = Duration::from_secs(15);
+
+/// The id of the last event a reconnecting client saw, from its `Last-Event-ID` header
+pub fn last_event_id(request: &HttpRequest) -> Option<String> {
+    request.get_header("Last-Event-ID")
+}
+
+/// A single server-sent event
+///
+/// `SseEvent::new("building").with_event("status").with_id("42")`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SseEvent {
+    data: String,
+    event: Option<String>,
+    id: Option<String>,
+    retry: Option<Duration>,
+}
+
+impl SseEvent {
+    /// An event with the default `message` type, data may span several lines
+    pub fn new(data: impl Into<String>) -> Self {
+        SseEvent { data: data.into(), event: None, id: None, retry: None }
+    }
+
+    /// The event type, which is what `addEventListener` listens for in the browser
+    pub fn with_event(mut self, event: &str) -> Self {
+        self.event = Some(single_line(event));
+        self
+    }
+
+    /// The id the client sends back as `Last-Event-ID` when it reconnects
+    pub fn with_id(mut self, id: &str) -> Self {
+        self.id = Some(single_line(id).replace('\0', ""));
+        self
+    }
+
+    /// How long the client waits before reconnecting after losing the stream
+    pub fn with_retry(mut self, retry: Duration) -> Self {
+        self.retry = Some(retry);
+        self
+    }
+
+    pub fn data(&self) -> &str {
+        &self.data
+    }
+
+    pub fn event(&self) -> Option<&str> {
+        self.event.as_deref()
+    }
+
+    pub fn id(&self) -> Option<&str> {
+        self.id.as_deref()
+    }
+}
+
+/// Field values other than data can't span lines, line breaks would start new fields
+fn single_line(value: &str) -> String {
+    value.replace(['\r', '\n'], "")
+}
+
+impl fmt::Display for SseEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if let Some(event) = &self.event {
+            writeln!(f, "event: {}", event)?;
+        }
+        if let Some(id) = &self.id {
+            writeln!(f, "id: {}", id)?;
+        }
+        if let Some(retry) = self.retry {
+            writeln!(f, "retry: {}", retry.as_millis())?;
+        }
+        // each line is its own data field, the client joins them back with \n
+        let data = self.data.replace("\r\n", "\n").replace('\r', "\n");
+        for line in data.split('\n') {
+            writeln!(f, "data: {}", line)?;
+        }
+        writeln!(f)
+    }
+}
+
+/// An open `text/event-stream` response, events are sent to the client as they happen
+pub struct EventStream<W: Write> {
+    body: BodyStream<W>,
+}
+
+impl<W: Write> EventStream<W> {
+    pub fn send(&mut self, event: &SseEvent) -> io::Result<()> {
+        self.body.send(event.to_string().as_bytes())
+    }
+
+    /// A comment line, ignored by the client
+    pub fn comment(&mut self, comment: &str) -> io::Result<()> {
+        self.body.send(format!(": {}\n\n", single_line(comment)).as_bytes())
+    }
+
+    /// Keeps an idle connection from being timed out, and finds out if the client has gone
+    pub fn keep_alive(&mut self) -> io::Result<()> {
+        self.comment("keep-alive")
+    }
+
+    /// Relays events from a subscription until the broadcaster closes, keeping the connection alive
+    /// while it is quiet, a client that has gone away ends it with an error
+    pub fn relay(mut self, events: &Receiver<SseEvent>, keep_alive: Duration) -> io::Result<()> {
+        loop {
+            match events.recv_timeout(keep_alive) {
+                Ok(event) => self.send(&event)?,
+                Err(RecvTimeoutError::Timeout) => self.keep_alive()?,
+                Err(RecvTimeoutError::Disconnected) => return self.finish(),
+            }
+        }
+    }
+
+    /// Ends the stream, the client will reconnect unless told otherwise
+    pub fn finish(self) -> io::Result<()> {
+        self.body.finish()
+    }
+}
+
+/// Responds 200 OK with an event stream, which is never cached
+///
+/// a handler feeding it from a broadcaster:
+/// `respond_event_stream(stream, request)?.relay(&EVENTS.subscribe_from(last_event_id(request).as_deref()), DEFAULT_KEEP_ALIVE)`
+pub fn respond_event_stream<'a>(stream: &'a mut ClientStream, request: &HttpRequest) -> io::Result<EventStream<&'a mut ClientStream>> {
+    start_event_stream(HttpResponse::for_request(RequestOk, request), stream)
+}
+
+fn start_event_stream<W: Write>(response: HttpResponse, writer: W) -> io::Result<EventStream<W>> {
+    let body = response
+        .header(HttpHeader::Custom("Cache-Control".to_string(), "no-cache".to_string()))
+        .stream(EVENT_STREAM, writer)?;
+    Ok(EventStream { body })
+}
+
+struct BroadcastState {
+    subscribers: Vec<Sender<SseEvent>>,
+    history: VecDeque<SseEvent>,
+    history_limit: usize,
+    closed: bool,
+}
+
+/// Feeds events from one producer to every connected client
+///
+/// clones share the same subscribers, so one can live in a `static` for handlers to subscribe to
+/// while a producer thread sends on another. Recent events can be kept so clients reconnecting
+/// with `Last-Event-ID` get what they missed
+#[derive(Clone)]
+pub struct Broadcaster {
+    state: Arc<Mutex<BroadcastState>>,
+}
+
+impl Default for Broadcaster {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Broadcaster {
+    pub fn new() -> Self {
+        Broadcaster {
+            state: Arc::new(Mutex::new(BroadcastState {
+                subscribers: Vec::new(),
+                history: VecDeque::new(),
+                history_limit: 0,
+                closed: false,
+            })),
+        }
+    }
+
+    /// Keep the last `limit` events to replay to reconnecting clients
+    pub fn with_history(self, limit: usize) -> Self {
+        self.lock().history_limit = limit;
+        self
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BroadcastState> {
+        // a panic while holding the lock can't leave the state half updated
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Sends an event to every subscriber, returning how many there were,
+    /// subscribers whose client has gone are dropped along the way
+    pub fn send(&self, event: SseEvent) -> usize {
+        let mut state = self.lock();
+        if state.closed {
+            return 0;
+        }
+        state.subscribers.retain(|subscriber| subscriber.send(event.clone()).is_ok());
+        if state.history_limit > 0 {
+            if state.history.len() == state.history_limit {
+                state.history.pop_front();
+            }
+            state.history.push_back(event);
+        }
+        state.subscribers.len()
+    }
+
+    /// Receive every event sent from now on
+    pub fn subscribe(&self) -> Receiver<SseEvent> {
+        self.subscribe_from(None)
+    }
+
+    /// Receive the kept events after the one with `last_event_id` first, then every new one,
+    /// an id no longer kept replays everything that is
+    pub fn subscribe_from(&self, last_event_id: Option<&str>) -> Receiver<SseEvent> {
+        let mut state = self.lock();
+        let (sender, receiver) = mpsc::channel();
+        if let Some(last_event_id) = last_event_id {
+            let missed = state.history.iter()
+                .position(|event| event.id() == Some(last_event_id))
+                .map_or(0, |position| position + 1);
+            for event in state.history.iter().skip(missed) {
+                let _ = sender.send(event.clone());
+            }
+        }
+        // subscribing after close gets the replay and then ends straight away
+        if !state.closed {
+            state.subscribers.push(sender);
+        }
+        receiver
+    }
+
+    pub fn subscriber_count(&self) -> usize {
+        self.lock().subscribers.len()
+    }
+
+    /// Ends every subscriber's stream once it has received what was already sent
+    pub fn close(&self) {
+        let mut state = self.lock();
+        state.closed = true;
+        state.subscribers.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_format() {
+        let event = SseEvent::new("line one\r\nline two\nline three")
+            .with_event("status\nid: injected")
+            .with_id("42")
+            .with_retry(Duration::from_secs(3));
+        assert_eq!(
+            event.to_string(),
+            "event: statusid: injected\nid: 42\nretry: 3000\ndata: line one\ndata: line two\ndata: line three\n\n"
+        );
+        assert_eq!(SseEvent::new("").to_string(), "data: \n\n");
+    }
+
+    #[test]
+    fn test_broadcast_and_resume() {
+        let broadcaster = Broadcaster::new().with_history(2);
+        let early = broadcaster.subscribe();
+        for id in ["1", "2", "3"] {
+            broadcaster.send(SseEvent::new(format!("event {}", id)).with_id(id));
+        }
+        let ids = |receiver: &Receiver<SseEvent>| -> Vec<String> {
+            receiver.try_iter().map(|event| event.id().unwrap().to_string()).collect()
+        };
+        assert_eq!(ids(&early), vec!["1", "2", "3"]);
+        // only the last two were kept
+        assert_eq!(ids(&broadcaster.subscribe_from(Some("2"))), vec!["3"]);
+        assert_eq!(ids(&broadcaster.subscribe_from(Some("1"))), vec!["2", "3"]);
+        assert_eq!(ids(&broadcaster.subscribe_from(None)), Vec::<String>::new());
+
+        // receivers that were dropped are pruned by the next send
+        let late = broadcaster.subscribe_from(Some("3"));
+        drop(early);
+        assert_eq!(broadcaster.send(SseEvent::new("4").with_id("4")), 1);
+        assert_eq!(broadcaster.subscriber_count(), 1);
+        assert_eq!(ids(&late), vec!["4"]);
+    }
+
+    #[test]
+    fn test_last_event_id() {
+        use crate::http_builder::{HttpMethod, HttpRequestLine};
+        let mut request = HttpRequest::new(HttpRequestLine::new(HttpMethod::GET, "/events"));
+        assert_eq!(last_event_id(&request), None);
+        request.add_header(HttpHeader::Custom("Last-Event-ID".to_string(), "42".to_string()));
+        assert_eq!(last_event_id(&request), Some("42".to_string()));
+    }
+
+    /// Collects what is written and signals the first time a keep-alive shows up in it
+    struct KeepAliveWatcher {
+        output: Vec<u8>,
+        keep_alive_seen: Option<Sender<()>>,
+    }
+
+    impl Write for KeepAliveWatcher {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.output.extend_from_slice(buf);
+            if self.output.windows(b": keep-alive".len()).any(|window| window == b": keep-alive")
+                && let Some(seen) = self.keep_alive_seen.take() {
+                let _ = seen.send(());
+            }
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_relay_until_closed() {
+        let broadcaster = Broadcaster::new();
+        let events = broadcaster.subscribe();
+        let producer = broadcaster.clone();
+        let (seen, keep_alive_seen) = mpsc::channel();
+        let handle = std::thread::spawn(move || {
+            producer.send(SseEvent::new("hello").with_event("greeting"));
+            // nothing else is sent until the relay has had to keep the stream alive
+            keep_alive_seen.recv().unwrap();
+            producer.send(SseEvent::new("bye"));
+            producer.close();
+        });
+
+        let mut watcher = KeepAliveWatcher { output: Vec::new(), keep_alive_seen: Some(seen) };
+        start_event_stream(HttpResponse::new(RequestOk), &mut watcher).unwrap()
+            .relay(&events, Duration::from_millis(1))
+            .unwrap();
+        handle.join().unwrap();
+
+        let text = String::from_utf8(watcher.output).unwrap();
+        let (head, body) = text.split_once("\r\n\r\n").unwrap();
+        assert!(head.contains("Content-Type: text/event-stream\r\n"));
+        assert!(head.contains("Cache-Control: no-cache\r\n"));
+        assert!(body.contains("event: greeting\ndata: hello\n\n"));
+        assert!(body.contains(": keep-alive\n\n"));
+        assert!(body.ends_with("data: bye\n\n\r\n0\r\n\r\n"));
+    }
+}
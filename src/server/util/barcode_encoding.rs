@@ -13,8 +13,7 @@ pub fn encode_to_barcode_bitstring(input: &str) -> Vec<bool> {
     
     //mapped characters into contiguous bitstring
     let mapped_in_binary = mapped_characters.iter()
-        .map(|num| first_11_unsigned_16_to_bitstring(*num))
-        .flatten()
+        .flat_map(|num| first_11_unsigned_16_to_bitstring(*num))
         .collect::<Vec<bool>>();
 
     //quiet zone
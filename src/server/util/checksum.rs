@@ -0,0 +1,72 @@
+//! CRC-32 (as used by gzip and PNG) and Adler-32 (as used by zlib)
+
+/// Lookup table for the reflected CRC-32 polynomial 0xEDB88320, one entry per byte value
+const CRC32_TABLE: [u32; 256] = crc32_table();
+
+const fn crc32_table() -> [u32; 256] {
+    let mut table = [0; 256];
+    let mut byte = 0;
+    while byte < 256 {
+        let mut crc = byte as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+            bit += 1;
+        }
+        table[byte] = crc;
+        byte += 1;
+    }
+    table
+}
+
+/// Continue a CRC-32 over more data, start from 0, `crc32_update(crc32(a), b) == crc32(a + b)`
+pub fn crc32_update(crc: u32, data: &[u8]) -> u32 {
+    let crc = data.iter().fold(!crc, |crc, byte| {
+        CRC32_TABLE[((crc ^ *byte as u32) & 0xff) as usize] ^ (crc >> 8)
+    });
+    !crc
+}
+
+pub fn crc32(data: &[u8]) -> u32 {
+    crc32_update(0, data)
+}
+
+/// Largest prime below 2^16
+const ADLER_MODULUS: u32 = 65521;
+
+/// Bytes that can be summed before the sums have to be reduced to stay within a u32
+const ADLER_CHUNK: usize = 5552;
+
+pub fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for chunk in data.chunks(ADLER_CHUNK) {
+        for byte in chunk {
+            a += *byte as u32;
+            b += a;
+        }
+        a %= ADLER_MODULUS;
+        b %= ADLER_MODULUS;
+    }
+    (b << 16) | a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(crc32(b"The quick brown fox jumps over the lazy dog"), 0x414F_A339);
+        assert_eq!(crc32_update(crc32(b"12345"), b"6789"), crc32(b"123456789"));
+    }
+
+    #[test]
+    fn test_adler32() {
+        assert_eq!(adler32(b""), 1);
+        assert_eq!(adler32(b"Wikipedia"), 0x11E6_0398);
+        // long enough for the sums to need reducing partway through
+        assert_eq!(adler32(&[0xff; 100_000]), 0x149A_302C);
+    }
+}
@@ -0,0 +1,187 @@
+use std::fs::Metadata;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::http_builder::{HttpHeader, HttpMethod, HttpRequest};
+use crate::http_builder::date::parse_http_date;
+
+/// Validators describing the current representation of a static file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileValidators {
+    /// Strong entity tag, including the surrounding quotes
+    pub etag: String,
+    /// Modification time truncated to whole seconds, the resolution of an HTTP-date
+    pub last_modified: Option<SystemTime>,
+}
+
+impl FileValidators {
+    /// Derive validators from the file's size and modification time
+    pub fn from_metadata(metadata: &Metadata) -> Self {
+        let modified = metadata.modified().ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok());
+
+        let etag = match modified {
+            Some(since_epoch) => format!("\"{:x}-{:x}{:08x}\"", metadata.len(), since_epoch.as_secs(), since_epoch.subsec_nanos()),
+            None => format!("\"{:x}\"", metadata.len()),
+        };
+
+        FileValidators {
+            etag,
+            last_modified: modified.map(|since_epoch| UNIX_EPOCH + Duration::from_secs(since_epoch.as_secs())),
+        }
+    }
+
+    /// Tag the ETag with the content coding of the bytes actually sent,
+    /// so a compressed and an uncompressed copy never share an entity tag
+    pub fn for_content_coding(mut self, coding: &str) -> Self {
+        let tag = self.etag.trim_end_matches('"');
+        self.etag = format!("{}-{}\"", tag, coding);
+        self
+    }
+
+    /// The ETag and Last-Modified headers to send along with the file
+    pub fn headers(&self) -> Vec<HttpHeader> {
+        let mut headers = vec![HttpHeader::Custom("ETag".to_string(), self.etag.clone())];
+        if let Some(last_modified) = self.last_modified {
+            headers.push(HttpHeader::LastModified(last_modified));
+        }
+        headers
+    }
+}
+
+/// Outcome of evaluating the conditional request headers
+#[derive(Debug, PartialEq, Eq)]
+pub enum Precondition {
+    /// Continue and serve the file (or the requested ranges)
+    Proceed,
+    /// 304 Not Modified, the client's cached copy is still current
+    NotModified,
+    /// 412 Precondition Failed
+    Failed,
+}
+
+/// Evaluate If-Match, If-Unmodified-Since, If-None-Match and If-Modified-Since
+/// in the order given by RFC 7232 section 6
+pub fn evaluate_preconditions(request: &HttpRequest, validators: &FileValidators) -> Precondition {
+    let is_get_or_head = matches!(request.method(), HttpMethod::GET | HttpMethod::HEAD);
+
+    if let Some(if_match) = request.get_header("If-Match") {
+        if !etag_list_matches(&if_match, &validators.etag, false) {
+            return Precondition::Failed;
+        }
+    } else if let Some(since) = request.headers().get_date("If-Unmodified-Since")
+        && let Some(last_modified) = validators.last_modified
+        && last_modified > since {
+        return Precondition::Failed;
+    }
+
+    if let Some(if_none_match) = request.get_header("If-None-Match") {
+        if etag_list_matches(&if_none_match, &validators.etag, true) {
+            return if is_get_or_head { Precondition::NotModified } else { Precondition::Failed };
+        }
+    } else if is_get_or_head
+        && let Some(since) = request.headers().get_date("If-Modified-Since")
+        && let Some(last_modified) = validators.last_modified
+        && last_modified <= since {
+        return Precondition::NotModified;
+    }
+
+    Precondition::Proceed
+}
+
+/// Whether the validator in an `If-Range` header still matches the file
+///
+/// entity tags use strong comparison, dates must match Last-Modified exactly
+pub fn if_range_matches(if_range: &str, validators: &FileValidators) -> bool {
+    let if_range = if_range.trim();
+    if if_range.starts_with('"') || if_range.starts_with("W/") {
+        return strong_compare(if_range, &validators.etag);
+    }
+    match (parse_http_date(if_range), validators.last_modified) {
+        (Some(date), Some(last_modified)) => date == last_modified,
+        _ => false,
+    }
+}
+
+/// Match an entity tag against a header value of `*` or a comma separated list of tags
+fn etag_list_matches(list: &str, etag: &str, weak: bool) -> bool {
+    if list.trim() == "*" {
+        return true;
+    }
+    list.split(',')
+        .map(str::trim)
+        .any(|candidate| if weak { weak_compare(candidate, etag) } else { strong_compare(candidate, etag) })
+}
+
+fn strong_compare(a: &str, b: &str) -> bool {
+    !a.starts_with("W/") && !b.starts_with("W/") && a == b
+}
+
+fn weak_compare(a: &str, b: &str) -> bool {
+    a.trim_start_matches("W/") == b.trim_start_matches("W/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http_builder::HttpRequestLine;
+
+    fn validators() -> FileValidators {
+        FileValidators {
+            etag: "\"1d-abc\"".to_string(),
+            last_modified: Some(UNIX_EPOCH + Duration::from_secs(784111777)),
+        }
+    }
+
+    fn request(method: HttpMethod, headers: &[(&str, &str)]) -> HttpRequest {
+        let mut request = HttpRequest::new(HttpRequestLine::new(method, "/file.txt"));
+        for (name, value) in headers {
+            request.add_header(HttpHeader::Custom(name.to_string(), value.to_string()));
+        }
+        request
+    }
+
+    #[test]
+    fn test_if_none_match() {
+        let v = validators();
+        assert_eq!(evaluate_preconditions(&request(HttpMethod::GET, &[("If-None-Match", "\"1d-abc\"")]), &v), Precondition::NotModified);
+        assert_eq!(evaluate_preconditions(&request(HttpMethod::GET, &[("If-None-Match", "W/\"1d-abc\"")]), &v), Precondition::NotModified);
+        assert_eq!(evaluate_preconditions(&request(HttpMethod::GET, &[("If-None-Match", "\"other\", \"1d-abc\"")]), &v), Precondition::NotModified);
+        assert_eq!(evaluate_preconditions(&request(HttpMethod::GET, &[("If-None-Match", "*")]), &v), Precondition::NotModified);
+        assert_eq!(evaluate_preconditions(&request(HttpMethod::GET, &[("If-None-Match", "\"other\"")]), &v), Precondition::Proceed);
+        assert_eq!(evaluate_preconditions(&request(HttpMethod::PUT, &[("If-None-Match", "*")]), &v), Precondition::Failed);
+    }
+
+    #[test]
+    fn test_if_modified_since() {
+        let v = validators();
+        assert_eq!(evaluate_preconditions(&request(HttpMethod::GET, &[("If-Modified-Since", "Sun, 06 Nov 1994 08:49:37 GMT")]), &v), Precondition::NotModified);
+        assert_eq!(evaluate_preconditions(&request(HttpMethod::GET, &[("If-Modified-Since", "Sun, 06 Nov 1994 08:49:36 GMT")]), &v), Precondition::Proceed);
+        assert_eq!(evaluate_preconditions(&request(HttpMethod::GET, &[("If-Modified-Since", "not a date")]), &v), Precondition::Proceed);
+        //If-None-Match takes precedence over If-Modified-Since
+        assert_eq!(evaluate_preconditions(&request(HttpMethod::GET, &[
+            ("If-None-Match", "\"other\""),
+            ("If-Modified-Since", "Sun, 06 Nov 1994 08:49:37 GMT"),
+        ]), &v), Precondition::Proceed);
+    }
+
+    #[test]
+    fn test_if_match_and_if_unmodified_since() {
+        let v = validators();
+        assert_eq!(evaluate_preconditions(&request(HttpMethod::GET, &[("If-Match", "\"1d-abc\"")]), &v), Precondition::Proceed);
+        assert_eq!(evaluate_preconditions(&request(HttpMethod::GET, &[("If-Match", "W/\"1d-abc\"")]), &v), Precondition::Failed);
+        assert_eq!(evaluate_preconditions(&request(HttpMethod::GET, &[("If-Match", "\"other\"")]), &v), Precondition::Failed);
+        assert_eq!(evaluate_preconditions(&request(HttpMethod::GET, &[("If-Match", "*")]), &v), Precondition::Proceed);
+        assert_eq!(evaluate_preconditions(&request(HttpMethod::GET, &[("If-Unmodified-Since", "Sun, 06 Nov 1994 08:49:36 GMT")]), &v), Precondition::Failed);
+        assert_eq!(evaluate_preconditions(&request(HttpMethod::GET, &[("If-Unmodified-Since", "Sun, 06 Nov 1994 08:49:37 GMT")]), &v), Precondition::Proceed);
+    }
+
+    #[test]
+    fn test_if_range_matches() {
+        let v = validators();
+        assert!(if_range_matches("\"1d-abc\"", &v));
+        assert!(!if_range_matches("W/\"1d-abc\"", &v));
+        assert!(!if_range_matches("\"other\"", &v));
+        assert!(if_range_matches("Sun, 06 Nov 1994 08:49:37 GMT", &v));
+        assert!(!if_range_matches("Sun, 06 Nov 1994 08:49:38 GMT", &v));
+    }
+}
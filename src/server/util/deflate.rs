@@ -0,0 +1,677 @@
+//! DEFLATE compression (RFC 1951), framed as zlib (RFC 1950) or gzip (RFC 1952)
+//!
+//! LZ77 matching over a 32 KiB window using hash chains, then each block is written
+//! with fixed Huffman codes, its own dynamic codes or stored as is, whichever is smallest.
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use super::checksum::{adler32, crc32};
+
+const WINDOW_SIZE: usize = 32 * 1024;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+const HASH_BITS: u32 = 15;
+/// How many earlier positions with the same hash are tried, more finds longer matches but is slower
+const MAX_CHAIN: usize = 128;
+/// A match at least this long is taken without checking whether the next position does better
+const GOOD_MATCH: usize = 32;
+/// Three byte matches further back than this cost more than the literals they replace
+const MAX_SHORT_MATCH_DISTANCE: usize = 4096;
+/// Symbols per block, each block gets its own Huffman codes
+const BLOCK_SYMBOLS: usize = 16 * 1024;
+const MAX_STORED_BLOCK: usize = 65535;
+const MAX_CODE_LENGTH: u8 = 15;
+const MAX_CODE_LENGTH_CODE_LENGTH: u8 = 7;
+
+const END_OF_BLOCK: usize = 256;
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258,
+];
+const LENGTH_EXTRA_BITS: [u8; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145,
+    8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA_BITS: [u8; 30] = [0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13];
+/// The order code length code lengths are written in, most often used first
+const CODE_LENGTH_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+/// Compress to a raw DEFLATE stream
+pub fn deflate(data: &[u8]) -> Vec<u8> {
+    let mut writer = BitWriter::default();
+    let tokens = Matcher::new(data).tokens();
+
+    let mut start = 0;
+    let mut blocks = tokens.chunks(BLOCK_SYMBOLS).peekable();
+    // no data still needs one (empty) final block
+    if blocks.peek().is_none() {
+        write_block(&mut writer, &[], &[], true);
+    }
+    while let Some(block) = blocks.next() {
+        let length: usize = block.iter().map(Token::len).sum();
+        write_block(&mut writer, block, &data[start..start + length], blocks.peek().is_none());
+        start += length;
+    }
+    writer.finish()
+}
+
+/// Compress to a zlib stream, the `deflate` content coding in HTTP
+pub fn zlib(data: &[u8]) -> Vec<u8> {
+    // 32 KiB window, default compression level, and a header check making 0x789c a multiple of 31
+    let mut out = vec![0x78, 0x9c];
+    out.extend(deflate(data));
+    out.extend(adler32(data).to_be_bytes());
+    out
+}
+
+/// Compress to a gzip member, the `gzip` content coding in HTTP
+pub fn gzip(data: &[u8]) -> Vec<u8> {
+    // magic, DEFLATE, no flags, no modification time, no extra flags, unknown OS
+    let mut out = vec![0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 255];
+    out.extend(deflate(data));
+    out.extend(crc32(data).to_le_bytes());
+    out.extend((data.len() as u32).to_le_bytes());
+    out
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Token {
+    Literal(u8),
+    Match { length: u16, distance: u16 },
+}
+
+impl Token {
+    /// How many bytes of input the token stands for
+    fn len(&self) -> usize {
+        match self {
+            Token::Literal(_) => 1,
+            Token::Match { length, .. } => *length as usize,
+        }
+    }
+}
+
+/// Finds LZ77 matches, remembering every earlier position by the hash of the 3 bytes starting there
+struct Matcher<'a> {
+    data: &'a [u8],
+    /// Most recent position for each hash
+    head: Vec<usize>,
+    /// The position before, with the same hash, indexed by position within the window
+    previous: Vec<usize>,
+}
+
+impl<'a> Matcher<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, head: vec![usize::MAX; 1 << HASH_BITS], previous: vec![usize::MAX; WINDOW_SIZE] }
+    }
+
+    fn hash(&self, position: usize) -> usize {
+        let bytes = u32::from_le_bytes([self.data[position], self.data[position + 1], self.data[position + 2], 0]);
+        (bytes.wrapping_mul(0x9E37_79B1) >> (32 - HASH_BITS)) as usize
+    }
+
+    fn insert(&mut self, position: usize) {
+        if position + MIN_MATCH <= self.data.len() {
+            let hash = self.hash(position);
+            self.previous[position % WINDOW_SIZE] = self.head[hash];
+            self.head[hash] = position;
+        }
+    }
+
+    /// The longest earlier match for the bytes at `position`, as (length, distance)
+    fn longest_match(&self, position: usize) -> (usize, usize) {
+        if position + MIN_MATCH > self.data.len() {
+            return (0, 0);
+        }
+        let max_length = MAX_MATCH.min(self.data.len() - position);
+        let mut best = (0, 0);
+        let mut candidate = self.head[self.hash(position)];
+        for _ in 0..MAX_CHAIN {
+            if candidate == usize::MAX || position - candidate > WINDOW_SIZE {
+                break;
+            }
+            // a longer match has to agree at the byte just past the best so far
+            if self.data[candidate + best.0] == self.data[position + best.0] {
+                let length = self.data[candidate..candidate + max_length].iter()
+                    .zip(&self.data[position..position + max_length])
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                if length > best.0 {
+                    best = (length, position - candidate);
+                    if length == max_length {
+                        break;
+                    }
+                }
+            }
+            let next = self.previous[candidate % WINDOW_SIZE];
+            // the slot has been reused by a later position, so the chain ends here
+            if next == usize::MAX || next >= candidate {
+                break;
+            }
+            candidate = next;
+        }
+        if best.0 < MIN_MATCH || (best.0 == MIN_MATCH && best.1 > MAX_SHORT_MATCH_DISTANCE) {
+            return (0, 0);
+        }
+        best
+    }
+
+    /// The whole input as literals and matches, deferring a match by a byte when that finds a longer one
+    fn tokens(mut self) -> Vec<Token> {
+        let mut tokens = Vec::with_capacity(self.data.len() / 2);
+        let mut position = 0;
+        while position < self.data.len() {
+            let (length, distance) = self.longest_match(position);
+            self.insert(position);
+            if length == 0 || (length < GOOD_MATCH && self.longest_match(position + 1).0 > length) {
+                tokens.push(Token::Literal(self.data[position]));
+                position += 1;
+                continue;
+            }
+            tokens.push(Token::Match { length: length as u16, distance: distance as u16 });
+            for covered in position + 1..position + length {
+                self.insert(covered);
+            }
+            position += length;
+        }
+        tokens
+    }
+}
+
+/// Index into the length tables, and the extra bits' value
+fn length_symbol(length: u16) -> (usize, u16) {
+    let index = LENGTH_BASE.partition_point(|base| *base <= length) - 1;
+    (index, length - LENGTH_BASE[index])
+}
+
+fn distance_symbol(distance: u16) -> (usize, u16) {
+    let index = DISTANCE_BASE.partition_point(|base| *base <= distance) - 1;
+    (index, distance - DISTANCE_BASE[index])
+}
+
+/// Writes bits least significant first, as DEFLATE packs them
+#[derive(Default)]
+struct BitWriter {
+    out: Vec<u8>,
+    buffer: u64,
+    bits: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, value: u32, count: u32) {
+        self.buffer |= (value as u64) << self.bits;
+        self.bits += count;
+        while self.bits >= 8 {
+            self.out.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.bits -= 8;
+        }
+    }
+
+    /// Pad with zeros to the next byte boundary
+    fn align(&mut self) {
+        if self.bits > 0 {
+            self.write(0, 8 - self.bits);
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        self.align();
+        self.out
+    }
+}
+
+/// Code lengths for an alphabet from how often each symbol is used, none longer than `max_length`
+///
+/// unused symbols get 0, except that at least two symbols always get a code, since decoders
+/// reject incomplete codes
+fn huffman_lengths(frequencies: &[u32], max_length: u8) -> Vec<u8> {
+    let mut symbols: Vec<usize> = (0..frequencies.len()).filter(|symbol| frequencies[*symbol] > 0).collect();
+    for symbol in 0..frequencies.len() {
+        if symbols.len() >= 2 {
+            break;
+        }
+        if !symbols.contains(&symbol) {
+            symbols.push(symbol);
+        }
+    }
+    let weight = |symbol: usize| frequencies[symbol].max(1) as u64;
+
+    // build the tree bottom up, leaves are 0..symbols.len(), internal nodes follow
+    let mut parents = vec![usize::MAX; symbols.len()];
+    let mut heap: BinaryHeap<Reverse<(u64, usize)>> = symbols.iter().enumerate()
+        .map(|(leaf, symbol)| Reverse((weight(*symbol), leaf)))
+        .collect();
+    while heap.len() > 1 {
+        let Reverse((weight_a, a)) = heap.pop().unwrap();
+        let Reverse((weight_b, b)) = heap.pop().unwrap();
+        let node = parents.len();
+        parents.push(usize::MAX);
+        parents[a] = node;
+        parents[b] = node;
+        heap.push(Reverse((weight_a + weight_b, node)));
+    }
+
+    let mut lengths = vec![0u8; frequencies.len()];
+    for (leaf, symbol) in symbols.iter().enumerate() {
+        let mut depth = 0;
+        let mut node = leaf;
+        while parents[node] != usize::MAX {
+            node = parents[node];
+            depth += 1;
+        }
+        lengths[*symbol] = depth.min(max_length as usize) as u8;
+    }
+
+    // clamping overfilled the code, measured in units of 2^-max_length
+    let capacity = 1u64 << max_length;
+    let mut used: u64 = symbols.iter().map(|symbol| 1 << (max_length - lengths[*symbol])).sum();
+    // lengthen the longest codes that can still grow, least used first, until the code fits
+    while used > capacity {
+        let symbol = *symbols.iter()
+            .filter(|symbol| lengths[**symbol] < max_length)
+            .max_by_key(|symbol| (lengths[**symbol], Reverse(weight(**symbol))))
+            .expect("a code over capacity has codes shorter than the limit");
+        used -= 1 << (max_length - lengths[symbol] - 1);
+        lengths[symbol] += 1;
+    }
+    // then give back any space that overshot, shortening the longest, most used codes
+    while used < capacity {
+        let symbol = *symbols.iter()
+            .max_by_key(|symbol| (lengths[**symbol], weight(**symbol)))
+            .expect("there are always two symbols");
+        used += 1 << (max_length - lengths[symbol]);
+        lengths[symbol] -= 1;
+    }
+    lengths
+}
+
+/// The canonical codes for a set of code lengths, bit reversed ready to be written least significant first
+fn canonical_codes(lengths: &[u8]) -> Vec<u16> {
+    let mut length_counts = [0u16; MAX_CODE_LENGTH as usize + 1];
+    for length in lengths.iter().filter(|length| **length > 0) {
+        length_counts[*length as usize] += 1;
+    }
+    let mut next_code = [0u16; MAX_CODE_LENGTH as usize + 2];
+    for length in 1..=MAX_CODE_LENGTH as usize {
+        next_code[length + 1] = (next_code[length] + length_counts[length]) << 1;
+    }
+    lengths.iter()
+        .map(|length| match *length {
+            0 => 0,
+            length => {
+                let code = next_code[length as usize];
+                next_code[length as usize] += 1;
+                code.reverse_bits() >> (16 - length)
+            }
+        })
+        .collect()
+}
+
+/// A Huffman code, the length of each symbol's code and the codes themselves
+struct HuffmanCode {
+    lengths: Vec<u8>,
+    codes: Vec<u16>,
+}
+
+impl HuffmanCode {
+    fn from_lengths(lengths: Vec<u8>) -> Self {
+        let codes = canonical_codes(&lengths);
+        Self { lengths, codes }
+    }
+
+    fn write(&self, writer: &mut BitWriter, symbol: usize) {
+        writer.write(self.codes[symbol] as u32, self.lengths[symbol] as u32);
+    }
+
+    /// The codes of block type 1, fixed by the format
+    fn fixed() -> (Self, Self) {
+        let literals = (0..288).map(|symbol| match symbol {
+            0..=143 => 8,
+            144..=255 => 9,
+            256..=279 => 7,
+            _ => 8,
+        }).collect();
+        (Self::from_lengths(literals), Self::from_lengths(vec![5; 30]))
+    }
+}
+
+/// How often each literal/length and distance symbol is used in a block
+fn symbol_frequencies(tokens: &[Token]) -> (Vec<u32>, Vec<u32>) {
+    let mut literals = vec![0u32; 286];
+    let mut distances = vec![0u32; 30];
+    literals[END_OF_BLOCK] = 1;
+    for token in tokens {
+        match token {
+            Token::Literal(byte) => literals[*byte as usize] += 1,
+            Token::Match { length, distance } => {
+                literals[257 + length_symbol(*length).0] += 1;
+                distances[distance_symbol(*distance).0] += 1;
+            }
+        }
+    }
+    (literals, distances)
+}
+
+/// Bits the tokens take with the given codes, including extra bits
+fn encoded_size(literal_frequencies: &[u32], distance_frequencies: &[u32], literals: &HuffmanCode, distances: &HuffmanCode) -> u64 {
+    let literal_bits: u64 = literal_frequencies.iter().enumerate()
+        .map(|(symbol, frequency)| {
+            let extra = if symbol > END_OF_BLOCK { LENGTH_EXTRA_BITS[symbol - 257] } else { 0 };
+            *frequency as u64 * (literals.lengths[symbol] + extra) as u64
+        })
+        .sum();
+    let distance_bits: u64 = distance_frequencies.iter().enumerate()
+        .map(|(symbol, frequency)| *frequency as u64 * (distances.lengths[symbol] + DISTANCE_EXTRA_BITS[symbol]) as u64)
+        .sum();
+    literal_bits + distance_bits
+}
+
+/// The header of a dynamic block, its code lengths run length encoded with their own Huffman code
+struct DynamicHeader {
+    literal_count: usize,
+    distance_count: usize,
+    code_length_count: usize,
+    /// Code length symbols 0 to 18, with the value and size of their extra bits
+    runs: Vec<(usize, u32, u32)>,
+    code_length_code: HuffmanCode,
+}
+
+impl DynamicHeader {
+    fn new(literals: &HuffmanCode, distances: &HuffmanCode) -> Self {
+        let used = |lengths: &[u8], minimum: usize| lengths.iter().rposition(|length| *length > 0).map_or(minimum, |last| (last + 1).max(minimum));
+        let literal_count = used(&literals.lengths, 257);
+        let distance_count = used(&distances.lengths, 1);
+        let lengths: Vec<u8> = literals.lengths[..literal_count].iter()
+            .chain(&distances.lengths[..distance_count])
+            .copied()
+            .collect();
+
+        let runs = run_length_encode(&lengths);
+        let mut frequencies = vec![0u32; 19];
+        for (symbol, _, _) in &runs {
+            frequencies[*symbol] += 1;
+        }
+        let code_length_code = HuffmanCode::from_lengths(huffman_lengths(&frequencies, MAX_CODE_LENGTH_CODE_LENGTH));
+        let code_length_count = CODE_LENGTH_ORDER.iter()
+            .rposition(|symbol| code_length_code.lengths[*symbol] > 0)
+            .map_or(4, |last| (last + 1).max(4));
+
+        Self { literal_count, distance_count, code_length_count, runs, code_length_code }
+    }
+
+    fn size(&self) -> u64 {
+        let runs: u64 = self.runs.iter()
+            .map(|(symbol, _, extra_bits)| (self.code_length_code.lengths[*symbol] as u32 + extra_bits) as u64)
+            .sum();
+        5 + 5 + 4 + 3 * self.code_length_count as u64 + runs
+    }
+
+    fn write(&self, writer: &mut BitWriter) {
+        writer.write((self.literal_count - 257) as u32, 5);
+        writer.write((self.distance_count - 1) as u32, 5);
+        writer.write((self.code_length_count - 4) as u32, 4);
+        for symbol in &CODE_LENGTH_ORDER[..self.code_length_count] {
+            writer.write(self.code_length_code.lengths[*symbol] as u32, 3);
+        }
+        for (symbol, extra, extra_bits) in &self.runs {
+            self.code_length_code.write(writer, *symbol);
+            writer.write(*extra, *extra_bits);
+        }
+    }
+}
+
+/// Code lengths as code length symbols, 16 repeats the previous length, 17 and 18 are runs of zeros
+fn run_length_encode(lengths: &[u8]) -> Vec<(usize, u32, u32)> {
+    let mut runs = Vec::new();
+    let mut index = 0;
+    while index < lengths.len() {
+        let length = lengths[index];
+        let mut run = lengths[index..].iter().take_while(|other| **other == length).count();
+        index += run;
+        if length == 0 {
+            while run >= 11 {
+                let take = run.min(138);
+                runs.push((18, (take - 11) as u32, 7));
+                run -= take;
+            }
+            if run >= 3 {
+                runs.push((17, (run - 3) as u32, 3));
+                run = 0;
+            }
+        } else {
+            runs.push((length as usize, 0, 0));
+            run -= 1;
+            while run >= 3 {
+                let take = run.min(6);
+                runs.push((16, (take - 3) as u32, 2));
+                run -= take;
+            }
+        }
+        runs.extend(std::iter::repeat_n((length as usize, 0, 0), run));
+    }
+    runs
+}
+
+/// Writes one block of tokens, `raw` being the input they encode, picking the smallest block type
+fn write_block(writer: &mut BitWriter, tokens: &[Token], raw: &[u8], is_final: bool) {
+    let (literal_frequencies, distance_frequencies) = symbol_frequencies(tokens);
+
+    let dynamic_literals = HuffmanCode::from_lengths(huffman_lengths(&literal_frequencies, MAX_CODE_LENGTH));
+    let dynamic_distances = HuffmanCode::from_lengths(huffman_lengths(&distance_frequencies, MAX_CODE_LENGTH));
+    let header = DynamicHeader::new(&dynamic_literals, &dynamic_distances);
+    let dynamic_size = header.size() + encoded_size(&literal_frequencies, &distance_frequencies, &dynamic_literals, &dynamic_distances);
+
+    let (fixed_literals, fixed_distances) = HuffmanCode::fixed();
+    let fixed_size = encoded_size(&literal_frequencies, &distance_frequencies, &fixed_literals, &fixed_distances);
+
+    // each stored block has its header padded to a byte, then 32 bits of length
+    let stored_blocks = raw.len().div_ceil(MAX_STORED_BLOCK).max(1) as u64;
+    let stored_size = raw.len() as u64 * 8 + stored_blocks * (3 + 7 + 32);
+
+    if stored_size < fixed_size.min(dynamic_size) {
+        write_stored(writer, raw, is_final);
+    } else if fixed_size <= dynamic_size {
+        writer.write(is_final as u32 | 1 << 1, 3);
+        write_tokens(writer, tokens, &fixed_literals, &fixed_distances);
+    } else {
+        writer.write(is_final as u32 | 2 << 1, 3);
+        header.write(writer);
+        write_tokens(writer, tokens, &dynamic_literals, &dynamic_distances);
+    }
+}
+
+fn write_stored(writer: &mut BitWriter, raw: &[u8], is_final: bool) {
+    let mut chunks = raw.chunks(MAX_STORED_BLOCK).peekable();
+    loop {
+        let chunk = chunks.next().unwrap_or(&[]);
+        let last = chunks.peek().is_none();
+        writer.write((is_final && last) as u32, 3);
+        writer.align();
+        writer.write(chunk.len() as u32, 16);
+        writer.write(!(chunk.len() as u16) as u32, 16);
+        writer.out.extend_from_slice(chunk);
+        if last {
+            break;
+        }
+    }
+}
+
+fn write_tokens(writer: &mut BitWriter, tokens: &[Token], literals: &HuffmanCode, distances: &HuffmanCode) {
+    for token in tokens {
+        match token {
+            Token::Literal(byte) => literals.write(writer, *byte as usize),
+            Token::Match { length, distance } => {
+                let (length_index, length_extra) = length_symbol(*length);
+                literals.write(writer, 257 + length_index);
+                writer.write(length_extra as u32, LENGTH_EXTRA_BITS[length_index] as u32);
+                let (distance_index, distance_extra) = distance_symbol(*distance);
+                distances.write(writer, distance_index);
+                writer.write(distance_extra as u32, DISTANCE_EXTRA_BITS[distance_index] as u32);
+            }
+        }
+    }
+    literals.write(writer, END_OF_BLOCK);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A minimal inflater, only here to check the encoder's output decodes back to its input
+    fn inflate(data: &[u8]) -> Vec<u8> {
+        struct Bits<'a> { data: &'a [u8], position: usize }
+        impl Bits<'_> {
+            fn read(&mut self, count: usize) -> u32 {
+                let mut value = 0;
+                for bit in 0..count {
+                    let position = self.position + bit;
+                    value |= (((self.data[position / 8] >> (position % 8)) & 1) as u32) << bit;
+                }
+                self.position += count;
+                value
+            }
+            fn symbol(&mut self, lengths: &[u8]) -> usize {
+                // walk the canonical code a bit at a time
+                let (mut code, mut first) = (0i32, 0i32);
+                for length in 1..=15 {
+                    code |= self.read(1) as i32;
+                    let count = lengths.iter().filter(|l| **l == length).count() as i32;
+                    if code - count < first {
+                        let mut symbols = (0..lengths.len()).filter(|s| lengths[*s] == length);
+                        return symbols.nth((code - first) as usize).unwrap();
+                    }
+                    first = (first + count) << 1;
+                    code <<= 1;
+                }
+                panic!("invalid code");
+            }
+        }
+
+        let mut bits = Bits { data, position: 0 };
+        let mut out = Vec::new();
+        loop {
+            let is_final = bits.read(1) == 1;
+            let (literals, distances) = match bits.read(2) {
+                0 => {
+                    bits.position = bits.position.div_ceil(8) * 8;
+                    let length = bits.read(16) as usize;
+                    assert_eq!(bits.read(16) as usize, !length & 0xffff);
+                    out.extend_from_slice(&data[bits.position / 8..bits.position / 8 + length]);
+                    bits.position += length * 8;
+                    if is_final { return out; }
+                    continue;
+                }
+                1 => {
+                    let (literals, distances) = HuffmanCode::fixed();
+                    (literals.lengths, distances.lengths)
+                }
+                2 => {
+                    let literal_count = bits.read(5) as usize + 257;
+                    let distance_count = bits.read(5) as usize + 1;
+                    let code_length_count = bits.read(4) as usize + 4;
+                    let mut code_length_lengths = [0u8; 19];
+                    for symbol in &CODE_LENGTH_ORDER[..code_length_count] {
+                        code_length_lengths[*symbol] = bits.read(3) as u8;
+                    }
+                    let mut lengths = Vec::new();
+                    while lengths.len() < literal_count + distance_count {
+                        match bits.symbol(&code_length_lengths) {
+                            16 => { let previous = *lengths.last().unwrap(); for _ in 0..3 + bits.read(2) { lengths.push(previous) } }
+                            17 => { for _ in 0..3 + bits.read(3) { lengths.push(0) } }
+                            18 => { for _ in 0..11 + bits.read(7) { lengths.push(0) } }
+                            length => lengths.push(length as u8),
+                        }
+                    }
+                    let distances = lengths.split_off(literal_count);
+                    (lengths, distances)
+                }
+                _ => panic!("reserved block type"),
+            };
+            loop {
+                let symbol = bits.symbol(&literals);
+                match symbol {
+                    0..=255 => out.push(symbol as u8),
+                    END_OF_BLOCK => break,
+                    _ => {
+                        let index = symbol - 257;
+                        let length = LENGTH_BASE[index] as usize + bits.read(LENGTH_EXTRA_BITS[index] as usize) as usize;
+                        let index = bits.symbol(&distances);
+                        let distance = DISTANCE_BASE[index] as usize + bits.read(DISTANCE_EXTRA_BITS[index] as usize) as usize;
+                        for _ in 0..length {
+                            out.push(out[out.len() - distance]);
+                        }
+                    }
+                }
+            }
+            if is_final { return out; }
+        }
+    }
+
+    /// Deterministic bytes that don't compress
+    fn noise(length: usize) -> Vec<u8> {
+        let mut state = 0x2545_F491_4F6C_DD1Du64;
+        (0..length).map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        }).collect()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let text = "<li>Lorem ipsum dolor sit amet, consectetur adipiscing elit</li>\n".repeat(500);
+        let mut mixed = noise(10_000);
+        mixed.extend(text.as_bytes());
+        mixed.extend(noise(300));
+        let inputs: Vec<Vec<u8>> = vec![
+            Vec::new(),
+            b"a".to_vec(),
+            b"abcabcabcabcabc".to_vec(),
+            text.clone().into_bytes(),
+            vec![0; 100_000],
+            noise(200_000),
+            mixed,
+        ];
+        for input in inputs {
+            assert_eq!(inflate(&deflate(&input)), input, "{} bytes", input.len());
+        }
+
+        assert!(deflate(text.as_bytes()).len() < text.len() / 20);
+        // incompressible input is stored, costing 5 bytes a block
+        assert!(deflate(&noise(200_000)).len() <= 200_000 + 5 * 200_000_usize.div_ceil(BLOCK_SYMBOLS));
+    }
+
+    #[test]
+    fn test_framing() {
+        let data = b"hello hello hello hello";
+        let zlib = zlib(data);
+        assert_eq!(&zlib[..2], &[0x78, 0x9c]);
+        assert_eq!(u16::from_be_bytes([zlib[0], zlib[1]]) % 31, 0);
+        assert_eq!(inflate(&zlib[2..zlib.len() - 4]), data);
+        assert_eq!(&zlib[zlib.len() - 4..], &adler32(data).to_be_bytes());
+
+        let gzip = gzip(data);
+        assert_eq!(&gzip[..3], &[0x1f, 0x8b, 8]);
+        assert_eq!(inflate(&gzip[10..gzip.len() - 8]), data);
+        assert_eq!(&gzip[gzip.len() - 8..gzip.len() - 4], &crc32(data).to_le_bytes());
+        assert_eq!(&gzip[gzip.len() - 4..], &(data.len() as u32).to_le_bytes());
+    }
+
+    #[test]
+    fn test_huffman_lengths() {
+        // fibonacci weights give the deepest possible tree, which has to be limited
+        let mut frequencies = vec![1u32, 1];
+        while frequencies.len() < 30 {
+            frequencies.push(frequencies[frequencies.len() - 1] + frequencies[frequencies.len() - 2]);
+        }
+        let lengths = huffman_lengths(&frequencies, 15);
+        assert!(lengths.iter().all(|length| (1..=15).contains(length)));
+        let kraft: u64 = lengths.iter().map(|length| 1u64 << (15 - length)).sum();
+        assert_eq!(kraft, 1 << 15);
+
+        assert_eq!(huffman_lengths(&[0, 5, 0], 7), vec![1, 1, 0]);
+        assert_eq!(huffman_lengths(&[1, 1, 2, 4], 7), vec![3, 3, 2, 1]);
+    }
+}
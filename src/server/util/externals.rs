@@ -36,7 +36,7 @@ impl ExternalRequest {
             match sender.send(response) {
                 Ok(_) => (),
                 Err(e) => {
-                    return Err(std::io::Error::new(std::io::ErrorKind::Other, e))
+                    return Err(std::io::Error::other(e))
                 }
             };
 
@@ -0,0 +1,71 @@
+/// Match a path against a glob pattern
+///
+/// * `*` matches any run of characters within a single path segment
+/// * `**` matches any run of characters, including `/`
+/// * `?` matches exactly one character other than `/`
+///
+/// everything else matches literally, the whole path has to be matched
+pub fn glob_matches(pattern: &str, path: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let path: Vec<char> = path.chars().collect();
+    matches_from(&pattern, &path)
+}
+
+fn matches_from(pattern: &[char], path: &[char]) -> bool {
+    match pattern.first() {
+        None => path.is_empty(),
+        Some('*') if pattern.get(1) == Some(&'*') => {
+            let mut rest = &pattern[2..];
+            // "**/" also matches no directories at all, e.g. "**/*.js" matches "app.js"
+            if rest.first() == Some(&'/') && matches_from(&rest[1..], path) {
+                return true;
+            }
+            while rest.first() == Some(&'*') {
+                rest = &rest[1..];
+            }
+            (0..=path.len()).any(|i| matches_from(rest, &path[i..]))
+        }
+        Some('*') => {
+            let rest = &pattern[1..];
+            for i in 0..=path.len() {
+                if matches_from(rest, &path[i..]) {
+                    return true;
+                }
+                if path.get(i) == Some(&'/') {
+                    break;
+                }
+            }
+            false
+        }
+        Some('?') => matches!(path.first(), Some(c) if *c != '/') && matches_from(&pattern[1..], &path[1..]),
+        Some(c) => path.first() == Some(c) && matches_from(&pattern[1..], &path[1..]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_literal_and_single_star() {
+        assert!(glob_matches("main.rs", "main.rs"));
+        assert!(!glob_matches("main.rs", "src/main.rs"));
+        assert!(glob_matches("*.rs", "main.rs"));
+        assert!(!glob_matches("*.rs", "server/main.rs"));
+        assert!(glob_matches("server/*.rs", "server/main.rs"));
+        assert!(glob_matches("*", ""));
+        assert!(glob_matches("file.?s", "file.js"));
+        assert!(!glob_matches("a?b", "a/b"));
+    }
+
+    #[test]
+    fn test_double_star() {
+        assert!(glob_matches("**/*.js", "app.js"));
+        assert!(glob_matches("**/*.js", "static/js/app.js"));
+        assert!(!glob_matches("**/*.js", "static/js/app.css"));
+        assert!(glob_matches("assets/**", "assets/img/logo.png"));
+        assert!(glob_matches("**/.git/**", ".git/config"));
+        assert!(glob_matches("a/**/b", "a/b"));
+        assert!(glob_matches("a/**/b", "a/x/y/b"));
+    }
+}
@@ -11,10 +11,6 @@ impl ByteHeader {
         ByteHeader(vec![0 ; size])
     }
 
-    pub fn size(&self) -> usize {
-        self.0.len()
-    }
-
     pub fn set_u16(&mut self, index: usize, value: u16) {
         self.0[index] = value as u8;
         self.0[index + 1] = (value >> 8) as u8;
@@ -30,10 +26,6 @@ impl ByteHeader {
     pub fn to_bytes(&self) -> Vec<u8> {
         self.0.clone()
     }
-    
-    fn get_u32(&self, index: usize) -> u32 {
-        self.0[index] as u32 | (self.0[index + 1] as u32) << 8 | (self.0[index + 2] as u32) << 16 | (self.0[index + 3] as u32) << 24
-    }
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -53,10 +45,6 @@ impl FileHeader {
         self.0.set_u32(2, (BITMAP_FILE_HEADER_SIZE + BITMAP_INFO_HEADER_SIZE) as u32 + size);
     }
 
-    pub fn get_file_size(&self) -> u32 {
-        self.0.get_u32(2)
-    }
-
     fn write_reserved(&mut self) {
         self.0.set_u32(6, 0);
     }
@@ -92,18 +80,10 @@ impl InfoHeader {
         self.0.set_u32(8, height);
     }
 
-    fn get_height(&self) -> u32 {
-        self.0.get_u32(8)
-    }
-
     fn write_width(&mut self, width: u32) {
         self.0.set_u32(4, width);
     }
 
-    fn get_width(&self) -> u32 {
-        self.0.get_u32(4)
-    }
-
     fn write_info_header_size(&mut self) {
         self.0.set_u32(0, BITMAP_INFO_HEADER_SIZE as u32);
     }
@@ -121,24 +101,16 @@ impl InfoHeader {
         self.0.set_u32(28, 1000);
     }
 
-    fn get_resolution(&self) -> (u32, u32) {
-        (self.0.get_u32(24), self.0.get_u32(28))
-    }
-
     fn write_num_colors(&mut self) {
         self.0.set_u32(36, 0);
     }
 
-    fn get_num_colors(&self) -> u32 {
-        self.0.get_u32(36)
-    }
-
     fn write_colors_used(&mut self) {
         self.0.set_u32(32, 0);
     }
 
     fn write_pixel_data_size(&mut self, width: u32, height: u32) {
-        self.0.set_u32(20, (3 * (width + get_padding(width)) * height) as u32);
+        self.0.set_u32(20, 3 * (width + get_padding(width)) * height);
     }
 
     pub fn update_info_header(&mut self, width: u32, height: u32, bits_per_pixel: u32) {
@@ -164,7 +136,6 @@ pub struct Bitmap {
     file_header: FileHeader,
     info_header: InfoHeader,
     pub image_buffer: ImageBuffer,
-    dimensions: (u32, u32),
 }
 
 impl Bitmap {
@@ -179,12 +150,11 @@ impl Bitmap {
             file_header,
             info_header,
             image_buffer: ImageBuffer::new(data, dimensions),
-            dimensions,
         }
     }
 
     pub fn write_bitmap(&self) -> Vec<u8> {
-        vec![self.file_header.0.to_bytes(), self.info_header.0.to_bytes(), self.image_buffer.to_bytes()].concat()
+        [self.file_header.0.to_bytes(), self.info_header.0.to_bytes(), self.image_buffer.to_bytes()].concat()
     }
 }
 
@@ -1,76 +1,395 @@
-use std::{fmt::Display, str::FromStr};
-
-#[derive(PartialEq, Debug, Eq, Copy, Clone)]
-pub enum MimeType {
-    Html,
-    Json,
-    PlainText,
-    JavaScript,
-    Css,
-    Jpeg,
-    Png,
-    Gif,
-    Bitmap,
-    CommaSeparatedValues,
-
-    Unknown,
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::{fmt::{self, Display}, str::FromStr};
+
+/// A media type, `type/subtype` with optional parameters, e.g. `text/html; charset=utf-8`
+///
+/// the type, subtype and parameter names are stored lowercase, so comparisons ignore their case
+#[derive(PartialEq, Debug, Eq, Clone, Hash)]
+pub struct MimeType {
+    main_type: Cow<'static, str>,
+    subtype: Cow<'static, str>,
+    parameters: Vec<(String, String)>,
+}
+
+impl MimeType {
+    pub const HTML: MimeType = MimeType::from_static("text", "html");
+    pub const JSON: MimeType = MimeType::from_static("application", "json");
+    pub const PLAIN_TEXT: MimeType = MimeType::from_static("text", "plain");
+    pub const JAVASCRIPT: MimeType = MimeType::from_static("text", "javascript");
+    pub const CSS: MimeType = MimeType::from_static("text", "css");
+    pub const CSV: MimeType = MimeType::from_static("text", "csv");
+    pub const XML: MimeType = MimeType::from_static("application", "xml");
+    pub const JPEG: MimeType = MimeType::from_static("image", "jpeg");
+    pub const PNG: MimeType = MimeType::from_static("image", "png");
+    pub const GIF: MimeType = MimeType::from_static("image", "gif");
+    pub const BITMAP: MimeType = MimeType::from_static("image", "bmp");
+    pub const SVG: MimeType = MimeType::from_static("image", "svg+xml");
+    pub const WASM: MimeType = MimeType::from_static("application", "wasm");
+    pub const OCTET_STREAM: MimeType = MimeType::from_static("application", "octet-stream");
+
+    /// For constants, both parts have to already be lowercase
+    pub const fn from_static(main_type: &'static str, subtype: &'static str) -> Self {
+        Self { main_type: Cow::Borrowed(main_type), subtype: Cow::Borrowed(subtype), parameters: Vec::new() }
+    }
+
+    pub fn new(main_type: &str, subtype: &str) -> Self {
+        Self {
+            main_type: Cow::Owned(main_type.to_ascii_lowercase()),
+            subtype: Cow::Owned(subtype.to_ascii_lowercase()),
+            parameters: Vec::new(),
+        }
+    }
+
+    /// The top level type, `image` in `image/svg+xml`
+    pub fn main_type(&self) -> &str {
+        &self.main_type
+    }
+
+    /// The whole subtype, `svg+xml` in `image/svg+xml`
+    pub fn subtype(&self) -> &str {
+        &self.subtype
+    }
+
+    /// The structured syntax suffix, `xml` in `image/svg+xml`
+    pub fn suffix(&self) -> Option<&str> {
+        self.subtype.rsplit_once('+').map(|(_, suffix)| suffix)
+    }
+
+    /// `type/subtype` without any parameters
+    pub fn essence(&self) -> String {
+        format!("{}/{}", self.main_type, self.subtype)
+    }
+
+    pub fn parameters(&self) -> &[(String, String)] {
+        &self.parameters
+    }
+
+    pub fn parameter(&self, name: &str) -> Option<&str> {
+        self.parameters.iter()
+            .find(|(parameter, _)| parameter.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn charset(&self) -> Option<&str> {
+        self.parameter("charset")
+    }
+
+    /// Sets a parameter, replacing any previous value for the same name
+    pub fn with_parameter(mut self, name: &str, value: &str) -> Self {
+        let name = name.to_ascii_lowercase();
+        self.parameters.retain(|(parameter, _)| *parameter != name);
+        self.parameters.push((name, value.to_string()));
+        self
+    }
+
+    pub fn with_charset(self, charset: &str) -> Self {
+        self.with_parameter("charset", charset)
+    }
+
+    pub fn without_parameters(&self) -> Self {
+        Self { main_type: self.main_type.clone(), subtype: self.subtype.clone(), parameters: Vec::new() }
+    }
+
+    /// Whether both are the same `type/subtype`, whatever their parameters
+    pub fn same_type(&self, other: &MimeType) -> bool {
+        self.main_type == other.main_type && self.subtype == other.subtype
+    }
 }
 
 impl FromStr for MimeType {
     type Err = ();
 
+    /// Parses `type/subtype *( ; name=value )`, values may be quoted strings
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s.to_lowercase().as_str() {
-            "text/html" => Ok(MimeType::Html),
-            "application/json" => Ok(MimeType::Json),
-            "text/plain" => Ok(MimeType::PlainText),
-            "text/javascript" => Ok(MimeType::JavaScript),
-            "text/css" => Ok(MimeType::Css),
-            "image/jpeg" => Ok(MimeType::Jpeg),
-            "image/png" => Ok(MimeType::Png),
-            "image/gif" => Ok(MimeType::Gif),
-            "image/bmp" => Ok(MimeType::Bitmap),
-            "text/csv" => Ok(MimeType::CommaSeparatedValues),
-
-            _ => Ok(MimeType::Unknown),
+        let (essence, mut rest) = s.split_once(';').unwrap_or((s, ""));
+        let (main_type, subtype) = essence.trim().split_once('/').ok_or(())?;
+        if !is_token(main_type) || !is_token(subtype) {
+            return Err(());
+        }
+
+        let mut mime_type = MimeType::new(main_type, subtype);
+        loop {
+            rest = rest.trim_start_matches([' ', '\t', ';']);
+            if rest.is_empty() {
+                return Ok(mime_type);
+            }
+            let (name, after) = rest.split_once('=').ok_or(())?;
+            if !is_token(name.trim()) {
+                return Err(());
+            }
+            let (value, remaining) = match after.trim_start().strip_prefix('"') {
+                Some(quoted) => parse_quoted_string(quoted)?,
+                None => {
+                    let (value, remaining) = after.split_once(';').unwrap_or((after, ""));
+                    (value.trim().to_string(), remaining)
+                }
+            };
+            mime_type = mime_type.with_parameter(name.trim(), &value);
+            rest = remaining;
         }
     }
 }
 
+/// Reads a quoted string up to its closing quote, which must end the parameter
+fn parse_quoted_string(quoted: &str) -> Result<(String, &str), ()> {
+    let mut value = String::new();
+    let mut chars = quoted.char_indices();
+    while let Some((index, c)) = chars.next() {
+        match c {
+            '\\' => value.push(chars.next().ok_or(())?.1),
+            '"' => {
+                let remaining = quoted[index + 1..].trim_start();
+                if !remaining.is_empty() && !remaining.starts_with(';') {
+                    return Err(());
+                }
+                return Ok((value, remaining));
+            }
+            c => value.push(c),
+        }
+    }
+    Err(())
+}
+
+fn is_token(s: &str) -> bool {
+    !s.is_empty() && s.bytes().all(|b| b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b))
+}
+
 impl Display for MimeType {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f, "{}", match self {
-                MimeType::Html => "text/html",
-                MimeType::Json => "application/json",
-                MimeType::PlainText => "text/plain",
-                MimeType::JavaScript => "text/javascript",
-                MimeType::Css => "text/css",
-                MimeType::Jpeg => "image/jpeg",
-                MimeType::Png => "image/png",
-                MimeType::Gif => "image/gif",
-                MimeType::Bitmap => "image/bmp",
-                MimeType::CommaSeparatedValues => "text/csv",
-                
-                _ => "Unknown", 
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.main_type, self.subtype)?;
+        for (name, value) in &self.parameters {
+            if is_token(value) {
+                write!(f, "; {}={}", name, value)?;
+            } else {
+                write!(f, "; {}=\"{}\"", name, value.replace('\\', "\\\\").replace('"', "\\\""))?;
             }
-        )
+        }
+        Ok(())
+    }
+}
+
+/// Built in types by file extension, extensions are lowercase and without the leading dot
+const EXTENSIONS: &[(&str, &str, &str)] = &[
+    // text
+    ("html", "text", "html"),
+    ("htm", "text", "html"),
+    ("css", "text", "css"),
+    ("js", "text", "javascript"),
+    ("mjs", "text", "javascript"),
+    ("txt", "text", "plain"),
+    ("text", "text", "plain"),
+    ("log", "text", "plain"),
+    ("md", "text", "markdown"),
+    ("markdown", "text", "markdown"),
+    ("csv", "text", "csv"),
+    ("tsv", "text", "tab-separated-values"),
+    ("ics", "text", "calendar"),
+    ("vcf", "text", "vcard"),
+    ("vtt", "text", "vtt"),
+    ("rtf", "application", "rtf"),
+    // structured data
+    ("json", "application", "json"),
+    ("map", "application", "json"),
+    ("jsonld", "application", "ld+json"),
+    ("webmanifest", "application", "manifest+json"),
+    ("geojson", "application", "geo+json"),
+    ("xml", "application", "xml"),
+    ("xsl", "application", "xml"),
+    ("xhtml", "application", "xhtml+xml"),
+    ("rss", "application", "rss+xml"),
+    ("atom", "application", "atom+xml"),
+    ("yaml", "application", "yaml"),
+    ("yml", "application", "yaml"),
+    ("toml", "application", "toml"),
+    ("wasm", "application", "wasm"),
+    // images
+    ("png", "image", "png"),
+    ("jpg", "image", "jpeg"),
+    ("jpeg", "image", "jpeg"),
+    ("jpe", "image", "jpeg"),
+    ("gif", "image", "gif"),
+    ("bmp", "image", "bmp"),
+    ("ico", "image", "vnd.microsoft.icon"),
+    ("cur", "image", "x-icon"),
+    ("svg", "image", "svg+xml"),
+    ("svgz", "image", "svg+xml"),
+    ("webp", "image", "webp"),
+    ("avif", "image", "avif"),
+    ("heic", "image", "heic"),
+    ("heif", "image", "heif"),
+    ("tif", "image", "tiff"),
+    ("tiff", "image", "tiff"),
+    ("apng", "image", "apng"),
+    ("jxl", "image", "jxl"),
+    // fonts
+    ("woff", "font", "woff"),
+    ("woff2", "font", "woff2"),
+    ("ttf", "font", "ttf"),
+    ("otf", "font", "otf"),
+    ("eot", "application", "vnd.ms-fontobject"),
+    // audio
+    ("mp3", "audio", "mpeg"),
+    ("wav", "audio", "wav"),
+    ("ogg", "audio", "ogg"),
+    ("oga", "audio", "ogg"),
+    ("opus", "audio", "opus"),
+    ("flac", "audio", "flac"),
+    ("aac", "audio", "aac"),
+    ("m4a", "audio", "mp4"),
+    ("weba", "audio", "webm"),
+    ("mid", "audio", "midi"),
+    ("midi", "audio", "midi"),
+    // video
+    ("mp4", "video", "mp4"),
+    ("m4v", "video", "mp4"),
+    ("webm", "video", "webm"),
+    ("ogv", "video", "ogg"),
+    ("mov", "video", "quicktime"),
+    ("avi", "video", "x-msvideo"),
+    ("mpeg", "video", "mpeg"),
+    ("mpg", "video", "mpeg"),
+    ("mkv", "video", "x-matroska"),
+    ("ts", "video", "mp2t"),
+    ("3gp", "video", "3gpp"),
+    // documents
+    ("pdf", "application", "pdf"),
+    ("doc", "application", "msword"),
+    ("docx", "application", "vnd.openxmlformats-officedocument.wordprocessingml.document"),
+    ("xls", "application", "vnd.ms-excel"),
+    ("xlsx", "application", "vnd.openxmlformats-officedocument.spreadsheetml.sheet"),
+    ("ppt", "application", "vnd.ms-powerpoint"),
+    ("pptx", "application", "vnd.openxmlformats-officedocument.presentationml.presentation"),
+    ("odt", "application", "vnd.oasis.opendocument.text"),
+    ("ods", "application", "vnd.oasis.opendocument.spreadsheet"),
+    ("odp", "application", "vnd.oasis.opendocument.presentation"),
+    ("epub", "application", "epub+zip"),
+    // archives
+    ("zip", "application", "zip"),
+    ("gz", "application", "gzip"),
+    ("tgz", "application", "gzip"),
+    ("bz2", "application", "x-bzip2"),
+    ("xz", "application", "x-xz"),
+    ("zst", "application", "zstd"),
+    ("br", "application", "x-brotli"),
+    ("tar", "application", "x-tar"),
+    ("7z", "application", "x-7z-compressed"),
+    ("rar", "application", "vnd.rar"),
+    ("jar", "application", "java-archive"),
+    // binaries
+    ("bin", "application", "octet-stream"),
+    ("exe", "application", "octet-stream"),
+    ("dll", "application", "octet-stream"),
+    ("iso", "application", "octet-stream"),
+    ("dmg", "application", "octet-stream"),
+    ("deb", "application", "vnd.debian.binary-package"),
+    ("apk", "application", "vnd.android.package-archive"),
+    ("sh", "application", "x-sh"),
+];
+
+/// The built in type for an extension, ignoring its case and any leading dot
+pub fn builtin_type(extension: &str) -> Option<MimeType> {
+    let extension = normalize_extension(extension);
+    EXTENSIONS.iter()
+        .find(|(known, _, _)| *known == extension)
+        .map(|(_, main_type, subtype)| MimeType::from_static(main_type, subtype))
+}
+
+fn normalize_extension(extension: &str) -> String {
+    extension.trim_start_matches('.').to_ascii_lowercase()
+}
+
+/// Media types by file extension, the built in table with any registered overrides on top
+///
+/// Built up with `with_type`, e.g.
+/// `MimeTypes::new().with_type("glsl", MimeType::PLAIN_TEXT)`
+#[derive(Debug, Clone, Default)]
+pub struct MimeTypes {
+    overrides: HashMap<String, MimeType>,
+}
+
+impl MimeTypes {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Serve files with this extension as `mime_type`, replacing the built in type if there is one
+    pub fn with_type(mut self, extension: &str, mime_type: MimeType) -> Self {
+        self.overrides.insert(normalize_extension(extension), mime_type);
+        self
+    }
+
+    pub fn get(&self, extension: &str) -> Option<MimeType> {
+        match self.overrides.get(&normalize_extension(extension)) {
+            Some(mime_type) => Some(mime_type.clone()),
+            None => builtin_type(extension),
+        }
+    }
+
+    /// The type for an extension, `application/octet-stream` when it isn't known
+    pub fn lookup(&self, extension: &str) -> MimeType {
+        self.get(extension).unwrap_or(MimeType::OCTET_STREAM)
     }
 }
 
+/// The built in type for a file extension, `application/octet-stream` when it isn't known,
+/// a server's overrides are looked up through its `MimeTypes` instead
 pub fn from_file_extension(extension: &str) -> MimeType {
-    match extension {
-        "html" => MimeType::Html,
-        "json" => MimeType::Json,
-        "txt" => MimeType::PlainText,
-        "js" => MimeType::JavaScript,
-        "css" => MimeType::Css,
-        "jpg" => MimeType::Jpeg,
-        "png" => MimeType::Png,
-        "gif" => MimeType::Gif,
-        "bmp" => MimeType::Bitmap,
-        "csv" => MimeType::CommaSeparatedValues,
-
-        _ => MimeType::Unknown,
-    }
-}
\ No newline at end of file
+    builtin_type(extension).unwrap_or(MimeType::OCTET_STREAM)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_display() {
+        assert_eq!("text/html".parse(), Ok(MimeType::HTML));
+        assert_eq!("Text/HTML".parse(), Ok(MimeType::HTML));
+
+        let mime_type: MimeType = "multipart/byteranges; boundary=3d6b6a416f9b5".parse().unwrap();
+        assert_eq!(mime_type.parameter("Boundary"), Some("3d6b6a416f9b5"));
+        assert_eq!(mime_type.to_string(), "multipart/byteranges; boundary=3d6b6a416f9b5");
+
+        let mime_type: MimeType = "text/plain;charset=\"utf-8\" ; format=\"a;b \\\"c\\\"\"".parse().unwrap();
+        assert_eq!(mime_type.charset(), Some("utf-8"));
+        assert_eq!(mime_type.parameter("format"), Some("a;b \"c\""));
+        assert_eq!(mime_type.to_string(), "text/plain; charset=utf-8; format=\"a;b \\\"c\\\"\"");
+
+        assert_eq!("Unknown".parse::<MimeType>(), Err(()));
+        assert_eq!("text/".parse::<MimeType>(), Err(()));
+        assert_eq!("text/plain; charset".parse::<MimeType>(), Err(()));
+        assert_eq!("text/plain; charset=\"utf-8".parse::<MimeType>(), Err(()));
+    }
+
+    #[test]
+    fn test_parts() {
+        assert_eq!(MimeType::SVG.main_type(), "image");
+        assert_eq!(MimeType::SVG.subtype(), "svg+xml");
+        assert_eq!(MimeType::SVG.suffix(), Some("xml"));
+        assert_eq!(MimeType::PNG.suffix(), None);
+
+        let html = MimeType::HTML.with_charset("utf-8");
+        assert_eq!(html.to_string(), "text/html; charset=utf-8");
+        assert_ne!(html, MimeType::HTML);
+        assert!(html.same_type(&MimeType::HTML));
+        assert_eq!(html.without_parameters(), MimeType::HTML);
+        assert_eq!(html.with_charset("iso-8859-1").to_string(), "text/html; charset=iso-8859-1");
+    }
+
+    #[test]
+    fn test_extensions() {
+        assert_eq!(builtin_type("wasm"), Some(MimeType::WASM));
+        assert_eq!(builtin_type(".WOFF2"), Some(MimeType::from_static("font", "woff2")));
+        assert_eq!(builtin_type("mp4").unwrap().to_string(), "video/mp4");
+        assert_eq!(builtin_type("nonsense"), None);
+
+        let mime_types = MimeTypes::new()
+            .with_type("js", MimeType::from_static("application", "javascript"))
+            .with_type(".glsl", MimeType::PLAIN_TEXT);
+        assert_eq!(mime_types.lookup("js").to_string(), "application/javascript");
+        assert_eq!(mime_types.lookup("GLSL"), MimeType::PLAIN_TEXT);
+        assert_eq!(mime_types.lookup("pdf").to_string(), "application/pdf");
+        assert_eq!(mime_types.lookup(""), MimeType::OCTET_STREAM);
+    }
+}
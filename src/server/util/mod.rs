@@ -4,3 +4,13 @@ pub mod mime_types;
 pub mod externals;
 pub mod barcode_encoding;
 pub mod images;
+pub mod range;
+pub mod conditional;
+pub mod glob;
+pub mod precompressed;
+pub mod sendfile;
+pub mod sniff;
+pub mod checksum;
+pub mod deflate;
+pub mod sha1;
+pub mod base64;
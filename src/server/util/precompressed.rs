@@ -0,0 +1,89 @@
+use std::fmt;
+use std::path::Path;
+
+use crate::http_builder::negotiation::{encoding_quality, parse_accept_encoding};
+
+/// Content codings the server can send, from precompressed sibling files or compressed on the fly
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentCoding {
+    Brotli,
+    Gzip,
+    /// zlib framed DEFLATE, only ever produced on the fly
+    Deflate,
+}
+
+impl ContentCoding {
+    /// Precompressed siblings, in order of preference when the client accepts several equally
+    pub const PREFERENCE: [ContentCoding; 2] = [ContentCoding::Brotli, ContentCoding::Gzip];
+
+    /// The token used in Accept-Encoding and Content-Encoding
+    pub fn token(&self) -> &'static str {
+        match self {
+            ContentCoding::Brotli => "br",
+            ContentCoding::Gzip => "gzip",
+            ContentCoding::Deflate => "deflate",
+        }
+    }
+
+    /// The suffix of the precompressed sibling, `app.js` -> `app.js.br`
+    pub fn file_suffix(&self) -> &'static str {
+        match self {
+            ContentCoding::Brotli => ".br",
+            ContentCoding::Gzip => ".gz",
+            ContentCoding::Deflate => ".zz",
+        }
+    }
+}
+
+impl fmt::Display for ContentCoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.token())
+    }
+}
+
+/// The codings the client accepts, best first, ties broken by the server's preference
+///
+/// a malformed header accepts none of them, as if it wasn't sent
+pub fn acceptable_codings(accept_encoding: &str) -> Vec<ContentCoding> {
+    let Some(accept_encoding) = parse_accept_encoding(accept_encoding) else {
+        return Vec::new();
+    };
+    let mut codings: Vec<_> = ContentCoding::PREFERENCE.iter()
+        .map(|coding| (*coding, encoding_quality(&accept_encoding, coding.token())))
+        .filter(|(_, quality)| quality.is_acceptable())
+        .collect();
+    // stable sort keeps the preference order for equal quality values
+    codings.sort_by_key(|(_, quality)| std::cmp::Reverse(*quality));
+    codings.into_iter().map(|(coding, _)| coding).collect()
+}
+
+/// Whether any precompressed sibling exists for the file, in which case responses vary on Accept-Encoding
+pub fn has_precompressed_sibling(path: &str) -> bool {
+    ContentCoding::PREFERENCE.iter()
+        .any(|coding| Path::new(&format!("{}{}", path, coding.file_suffix())).is_file())
+}
+
+/// Find the best precompressed sibling of `path` the client will accept
+pub fn find_precompressed(path: &str, accept_encoding: Option<&str>) -> Option<(String, ContentCoding)> {
+    let accept_encoding = accept_encoding?;
+    acceptable_codings(accept_encoding).into_iter()
+        .map(|coding| (format!("{}{}", path, coding.file_suffix()), coding))
+        .find(|(sibling, _)| Path::new(sibling).is_file())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_acceptable_codings() {
+        assert_eq!(acceptable_codings("gzip, deflate, br"), vec![ContentCoding::Brotli, ContentCoding::Gzip]);
+        assert_eq!(acceptable_codings("gzip;q=1.0, br;q=0.5"), vec![ContentCoding::Gzip, ContentCoding::Brotli]);
+        assert_eq!(acceptable_codings("gzip"), vec![ContentCoding::Gzip]);
+        assert_eq!(acceptable_codings("br;q=0, *"), vec![ContentCoding::Gzip]);
+        assert_eq!(acceptable_codings("identity"), vec![]);
+        assert_eq!(acceptable_codings("*;q=0"), vec![]);
+        assert_eq!(acceptable_codings("x-gzip"), vec![ContentCoding::Gzip]);
+        assert_eq!(acceptable_codings("gzip;q=2, br"), vec![]);
+    }
+}
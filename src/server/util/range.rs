@@ -0,0 +1,172 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Upper bound on the number of ranges accepted in a single `Range` header,
+/// anything above this is treated as malformed and the full file is served instead
+const MAX_RANGES: usize = 32;
+
+/// An inclusive byte range, already resolved against the length of the file it applies to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ByteRange {
+    pub start: u64,
+    pub end: u64,
+}
+
+impl ByteRange {
+    pub fn new(start: u64, end: u64) -> Self {
+        ByteRange { start, end }
+    }
+
+    pub fn len(&self) -> u64 {
+        self.end - self.start + 1
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.end < self.start
+    }
+
+    /// Value for the `Content-Range` header, e.g. `bytes 0-499/1234`
+    pub fn content_range(&self, total_length: u64) -> String {
+        format!("bytes {}-{}/{}", self.start, self.end, total_length)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum RangeError {
+    /// The header could not be understood, it should be ignored and the full file served
+    Malformed,
+    /// The header was valid but none of the ranges overlap the file, respond 416
+    Unsatisfiable,
+}
+
+/// Parse the value of a `Range` header against a file of `length` bytes
+///
+/// Supports `first-last`, `first-` and `-suffix` specs, separated by commas.
+/// Ranges that start past the end of the file are dropped, and if none are left
+/// the whole header is unsatisfiable.
+pub fn parse_range_header(value: &str, length: u64) -> Result<Vec<ByteRange>, RangeError> {
+    let (unit, specs) = value.split_once('=').ok_or(RangeError::Malformed)?;
+    if !unit.trim().eq_ignore_ascii_case("bytes") {
+        return Err(RangeError::Malformed);
+    }
+
+    let mut ranges = Vec::new();
+    let mut spec_count = 0;
+    for spec in specs.split(',') {
+        let spec = spec.trim();
+        // empty list elements are allowed by the grammar, e.g. "bytes=0-1,,5-6"
+        if spec.is_empty() {
+            continue;
+        }
+        spec_count += 1;
+        if spec_count > MAX_RANGES {
+            return Err(RangeError::Malformed);
+        }
+
+        let (first, last) = spec.split_once('-').ok_or(RangeError::Malformed)?;
+        let (first, last) = (first.trim(), last.trim());
+
+        if first.is_empty() {
+            // suffix range: the last N bytes of the file
+            let suffix = parse_position(last)?;
+            if suffix == 0 || length == 0 {
+                continue;
+            }
+            ranges.push(ByteRange::new(length.saturating_sub(suffix), length - 1));
+            continue;
+        }
+
+        let start = parse_position(first)?;
+        let end = if last.is_empty() { None } else { Some(parse_position(last)?) };
+        if let Some(end) = end && end < start {
+            return Err(RangeError::Malformed);
+        }
+        if start >= length {
+            continue;
+        }
+        let end = end.map_or(length - 1, |end| end.min(length - 1));
+        ranges.push(ByteRange::new(start, end));
+    }
+
+    if spec_count == 0 {
+        return Err(RangeError::Malformed);
+    }
+    if ranges.is_empty() {
+        return Err(RangeError::Unsatisfiable);
+    }
+    Ok(ranges)
+}
+
+fn parse_position(value: &str) -> Result<u64, RangeError> {
+    if value.is_empty() || !value.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(RangeError::Malformed);
+    }
+    value.parse::<u64>().map_err(|_| RangeError::Malformed)
+}
+
+/// Generate a boundary for a `multipart/byteranges` body
+///
+/// Only needs to be unlikely to appear in the file contents, not unpredictable
+pub fn multipart_boundary() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or(0);
+    let count = COUNTER.fetch_add(1, Ordering::Relaxed);
+    format!("byteranges_{:016x}{:08x}", nanos, count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_single_ranges() {
+        assert_eq!(parse_range_header("bytes=0-499", 1000), Ok(vec![ByteRange::new(0, 499)]));
+        assert_eq!(parse_range_header("bytes=500-", 1000), Ok(vec![ByteRange::new(500, 999)]));
+        assert_eq!(parse_range_header("bytes=-200", 1000), Ok(vec![ByteRange::new(800, 999)]));
+        assert_eq!(parse_range_header("bytes=900-2000", 1000), Ok(vec![ByteRange::new(900, 999)]));
+        assert_eq!(parse_range_header("bytes=-5000", 1000), Ok(vec![ByteRange::new(0, 999)]));
+        assert_eq!(parse_range_header("Bytes = 1-1", 10), Ok(vec![ByteRange::new(1, 1)]));
+    }
+
+    #[test]
+    fn test_multiple_ranges() {
+        assert_eq!(
+            parse_range_header("bytes=0-9, 20-29,-5", 100),
+            Ok(vec![ByteRange::new(0, 9), ByteRange::new(20, 29), ByteRange::new(95, 99)])
+        );
+        //unsatisfiable ranges are dropped as long as one remains
+        assert_eq!(parse_range_header("bytes=0-9,200-300", 100), Ok(vec![ByteRange::new(0, 9)]));
+        assert_eq!(parse_range_header("bytes=0-1,,5-6", 10), Ok(vec![ByteRange::new(0, 1), ByteRange::new(5, 6)]));
+    }
+
+    #[test]
+    fn test_unsatisfiable_ranges() {
+        assert_eq!(parse_range_header("bytes=1000-", 1000), Err(RangeError::Unsatisfiable));
+        assert_eq!(parse_range_header("bytes=-0", 1000), Err(RangeError::Unsatisfiable));
+        assert_eq!(parse_range_header("bytes=0-10", 0), Err(RangeError::Unsatisfiable));
+        assert_eq!(parse_range_header("bytes=2000-3000,5000-", 1000), Err(RangeError::Unsatisfiable));
+    }
+
+    #[test]
+    fn test_malformed_ranges() {
+        assert_eq!(parse_range_header("items=0-10", 1000), Err(RangeError::Malformed));
+        assert_eq!(parse_range_header("bytes=10-5", 1000), Err(RangeError::Malformed));
+        assert_eq!(parse_range_header("bytes=abc", 1000), Err(RangeError::Malformed));
+        assert_eq!(parse_range_header("bytes=+1-5", 1000), Err(RangeError::Malformed));
+        assert_eq!(parse_range_header("bytes=", 1000), Err(RangeError::Malformed));
+        assert_eq!(parse_range_header("0-10", 1000), Err(RangeError::Malformed));
+
+        let too_many = format!("bytes={}", vec!["0-0"; MAX_RANGES + 1].join(","));
+        assert_eq!(parse_range_header(&too_many, 1000), Err(RangeError::Malformed));
+    }
+
+    #[test]
+    fn test_content_range() {
+        let range = ByteRange::new(0, 499);
+        assert_eq!(range.len(), 500);
+        assert_eq!(range.content_range(1234), "bytes 0-499/1234");
+    }
+}
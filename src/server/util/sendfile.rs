@@ -0,0 +1,151 @@
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::net::TcpStream;
+
+/// Size of the userspace buffer used when the kernel can't copy for us
+const COPY_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Send `length` bytes of `file`, starting at `offset`, to the stream
+///
+/// On 64-bit Linux the kernel copies straight from the page cache to the socket with
+/// `sendfile(2)`, anywhere else (or if the kernel refuses) this falls back to `copy_buffered`
+pub fn send_file(stream: &mut TcpStream, file: &mut File, offset: u64, length: u64) -> io::Result<()> {
+    #[cfg(all(target_os = "linux", target_pointer_width = "64"))]
+    {
+        match linux::sendfile_all(stream, file, offset, length) {
+            Ok(sent) if sent == length => return Ok(()),
+            // the file shrank while it was being sent, the Content-Length already promised more
+            Ok(_) => return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "file truncated while sending")),
+            Err(e) if linux::is_unsupported(&e) => {}
+            Err(e) => return Err(e),
+        }
+    }
+    copy_buffered(stream, file, offset, length)
+}
+
+/// Copy `length` bytes of `file`, starting at `offset`, through a userspace buffer
+///
+/// works for any writer, e.g. a stream wrapped in an encryption layer
+pub fn copy_buffered<W: Write>(writer: &mut W, file: &mut File, offset: u64, length: u64) -> io::Result<()> {
+    file.seek(SeekFrom::Start(offset))?;
+    let mut reader = file.take(length);
+    let mut buffer = vec![0; COPY_BUFFER_SIZE];
+    let mut copied = 0;
+    loop {
+        let size = match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(size) => size,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        writer.write_all(&buffer[..size])?;
+        copied += size as u64;
+    }
+    if copied != length {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "file truncated while sending"));
+    }
+    Ok(())
+}
+
+#[cfg(all(target_os = "linux", target_pointer_width = "64"))]
+mod linux {
+    use std::fs::File;
+    use std::io;
+    use std::net::TcpStream;
+    use std::os::fd::AsRawFd;
+
+    /// Largest count a single sendfile call will transfer
+    const MAX_SENDFILE_CHUNK: u64 = 0x7fff_f000;
+
+    const EINVAL: i32 = 22;
+    const ENOSYS: i32 = 38;
+
+    unsafe extern "C" {
+        // off_t is 64 bits on 64-bit Linux
+        fn sendfile(out_fd: i32, in_fd: i32, offset: *mut i64, count: usize) -> isize;
+    }
+
+    /// Call sendfile until `length` bytes are sent or the file ends, returning the bytes sent
+    pub fn sendfile_all(stream: &TcpStream, file: &File, offset: u64, length: u64) -> io::Result<u64> {
+        let mut position = i64::try_from(offset).map_err(|_| io::Error::from(io::ErrorKind::InvalidInput))?;
+        let mut sent = 0;
+        while sent < length {
+            let count = (length - sent).min(MAX_SENDFILE_CHUNK) as usize;
+            // SAFETY: both descriptors are open for the duration of the call and `position`
+            // is a valid, exclusively borrowed off_t the kernel advances by the bytes sent
+            let result = unsafe { sendfile(stream.as_raw_fd(), file.as_raw_fd(), &mut position, count) };
+            match result {
+                0 => break,
+                n if n > 0 => sent += n as u64,
+                _ => {
+                    let error = io::Error::last_os_error();
+                    if error.kind() == io::ErrorKind::Interrupted {
+                        continue;
+                    }
+                    // once bytes are on the wire a buffered fallback would send them again
+                    if sent > 0 && is_unsupported(&error) {
+                        return Err(io::Error::other(error));
+                    }
+                    return Err(error);
+                }
+            }
+        }
+        Ok(sent)
+    }
+
+    /// Errors meaning sendfile can't be used for these descriptors at all
+    pub fn is_unsupported(error: &io::Error) -> bool {
+        matches!(error.raw_os_error(), Some(EINVAL) | Some(ENOSYS))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+    use std::net::TcpListener;
+    use std::thread;
+
+    fn temp_file(contents: &[u8]) -> (std::path::PathBuf, File) {
+        let path = std::env::temp_dir().join(format!("http_server_sendfile_{}_{}", std::process::id(), contents.len()));
+        std::fs::write(&path, contents).unwrap();
+        let file = File::open(&path).unwrap();
+        (path, file)
+    }
+
+    fn send_over_socket(contents: &[u8], offset: u64, length: u64) -> Vec<u8> {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let reader = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut received = Vec::new();
+            stream.read_to_end(&mut received).unwrap();
+            received
+        });
+
+        let (path, mut file) = temp_file(contents);
+        let mut stream = TcpStream::connect(address).unwrap();
+        send_file(&mut stream, &mut file, offset, length).unwrap();
+        drop(stream);
+        std::fs::remove_file(path).unwrap();
+        reader.join().unwrap()
+    }
+
+    #[test]
+    fn test_send_file() {
+        let contents: Vec<u8> = (0..200_000u32).map(|i| i as u8).collect();
+        assert_eq!(send_over_socket(&contents, 0, contents.len() as u64), contents);
+        assert_eq!(send_over_socket(&contents, 1000, 5000), contents[1000..6000].to_vec());
+    }
+
+    #[test]
+    fn test_copy_buffered() {
+        let contents = b"0123456789".to_vec();
+        let (path, mut file) = temp_file(&contents);
+        let mut output = Vec::new();
+        copy_buffered(&mut output, &mut file, 2, 5).unwrap();
+        assert_eq!(output, b"23456");
+        assert!(copy_buffered(&mut Vec::new(), &mut file, 8, 5).is_err());
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,88 @@
+use std::fs::File;
+use std::io::{self, Read};
+
+use crate::server::util::mime_types::MimeType;
+
+/// How much of a file is looked at, enough for every signature and a fair sample of text
+pub const SNIFF_LENGTH: usize = 512;
+
+/// Leading bytes identifying a format, checked in order
+const SIGNATURES: &[(&[u8], MimeType)] = &[
+    (b"\x89PNG\r\n\x1a\n", MimeType::PNG),
+    (b"\xff\xd8\xff", MimeType::JPEG),
+    (b"GIF87a", MimeType::GIF),
+    (b"GIF89a", MimeType::GIF),
+    (b"BM", MimeType::BITMAP),
+    (b"%PDF-", MimeType::from_static("application", "pdf")),
+    (b"PK\x03\x04", MimeType::from_static("application", "zip")),
+    // an empty archive has only the end of central directory record
+    (b"PK\x05\x06", MimeType::from_static("application", "zip")),
+    (b"\0asm", MimeType::WASM),
+];
+
+/// Guess the type of content from its first bytes, for files whose extension doesn't say
+///
+/// known signatures win, then anything that decodes as UTF-8 without control characters is
+/// `text/plain; charset=utf-8`, and the rest is `application/octet-stream`
+pub fn sniff_mime_type(bytes: &[u8]) -> MimeType {
+    let bytes = &bytes[..bytes.len().min(SNIFF_LENGTH)];
+    if let Some((_, mime_type)) = SIGNATURES.iter().find(|(signature, _)| bytes.starts_with(signature)) {
+        return mime_type.clone();
+    }
+    if is_text(bytes) {
+        return MimeType::PLAIN_TEXT.with_charset("utf-8");
+    }
+    MimeType::OCTET_STREAM
+}
+
+/// Sniff the type of the file at `path` from its start
+pub fn sniff_file(path: &str) -> io::Result<MimeType> {
+    let mut start = Vec::with_capacity(SNIFF_LENGTH);
+    File::open(path)?.take(SNIFF_LENGTH as u64).read_to_end(&mut start)?;
+    Ok(sniff_mime_type(&start))
+}
+
+/// Valid UTF-8, allowing a character cut off by the sample's end, with no control characters
+/// other than whitespace, form feed and escape
+fn is_text(bytes: &[u8]) -> bool {
+    let bytes = bytes.strip_prefix(b"\xef\xbb\xbf").unwrap_or(bytes);
+    let valid_length = match std::str::from_utf8(bytes) {
+        Ok(_) => bytes.len(),
+        // `error_len` is `None` when the input ends partway through a character
+        Err(e) if e.error_len().is_none() => e.valid_up_to(),
+        Err(_) => return false,
+    };
+    !bytes[..valid_length].iter().any(|b| b.is_ascii_control() && !b"\t\n\r\x0c\x1b".contains(b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signatures() {
+        assert_eq!(sniff_mime_type(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR"), MimeType::PNG);
+        assert_eq!(sniff_mime_type(b"\xff\xd8\xff\xe0\0\x10JFIF"), MimeType::JPEG);
+        assert_eq!(sniff_mime_type(b"GIF89a\x01\0\x01\0"), MimeType::GIF);
+        assert_eq!(sniff_mime_type(b"BM\x36\0\x0c\0\0\0"), MimeType::BITMAP);
+        assert_eq!(sniff_mime_type(b"%PDF-1.7\n%\xe2\xe3\xcf\xd3").to_string(), "application/pdf");
+        assert_eq!(sniff_mime_type(b"PK\x03\x04\x14\0\0\0").to_string(), "application/zip");
+        assert_eq!(sniff_mime_type(b"\0asm\x01\0\0\0"), MimeType::WASM);
+    }
+
+    #[test]
+    fn test_text_and_binary() {
+        let text = MimeType::PLAIN_TEXT.with_charset("utf-8");
+        assert_eq!(sniff_mime_type(b"all: build\n\tcargo build\r\n"), text);
+        assert_eq!(sniff_mime_type("\u{feff}h\u{e9}llo w\u{f6}rld \u{1f980}".as_bytes()), text);
+        assert_eq!(sniff_mime_type(b""), text);
+        // a character cut in half by the sample's end is still text
+        let mut long = "\u{e9}".repeat(SNIFF_LENGTH / 2 - 1).into_bytes();
+        long.extend_from_slice("x\u{e9}".as_bytes());
+        assert_eq!(sniff_mime_type(&long), text);
+
+        assert_eq!(sniff_mime_type(b"\x7fELF\x02\x01\x01\0"), MimeType::OCTET_STREAM);
+        assert_eq!(sniff_mime_type(b"caf\xe9 latin-1"), MimeType::OCTET_STREAM);
+        assert_eq!(sniff_mime_type(b"text with a\0nul"), MimeType::OCTET_STREAM);
+    }
+}
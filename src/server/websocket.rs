@@ -0,0 +1,625 @@
+use std::fmt::{self, Formatter};
+use std::io::{self, Read, Write};
+
+use crate::server::client_stream::ClientStream;
+use crate::http_builder::{HttpHeader, HttpRequest, HttpResponse, HttpStatus, HttpVersion};
+use crate::server::error::ServerError;
+use crate::server::util::base64;
+use crate::server::util::sha1::sha1;
+
+/// Appended to the client's key before hashing, fixed by RFC 6455
+const HANDSHAKE_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// The only protocol version, anything else is refused with this one advertised
+const WEBSOCKET_VERSION: &str = "13";
+
+/// Largest message, after joining its fragments, that is accepted before closing with 1009
+pub const DEFAULT_MAX_MESSAGE_SIZE: usize = 16 * 1024 * 1024;
+
+/// Control frames carry at most this much, so they fit in a frame without an extended length
+const MAX_CONTROL_PAYLOAD: usize = 125;
+
+/// The `Sec-WebSocket-Accept` answering a client's `Sec-WebSocket-Key`
+pub fn accept_key(key: &str) -> String {
+    base64::encode(&sha1(format!("{}{}", key.trim(), HANDSHAKE_GUID).as_bytes()))
+}
+
+/// Whether a list header such as `Connection: keep-alive, Upgrade` names the token
+fn has_token(request: &HttpRequest, header: &str, token: &str) -> bool {
+    request.headers().get_list(header).iter().any(|value| value.eq_ignore_ascii_case(token))
+}
+
+/// Completes the opening handshake for a WebSocket route, answering `101 Switching Protocols`
+///
+/// a plain request, or one for another protocol version, is answered `426 Upgrade Required`
+/// naming what is supported and gives `None`, the handler isn't run
+pub fn accept_upgrade<'a>(stream: &'a mut ClientStream, request: &HttpRequest) -> Result<Option<WebSocket<&'a mut ClientStream>>, ServerError> {
+    let upgrade_requested = has_token(request, "Upgrade", "websocket") && has_token(request, "Connection", "upgrade");
+    if !upgrade_requested || request.get_header("Sec-WebSocket-Version").as_deref().map(str::trim) != Some(WEBSOCKET_VERSION) {
+        HttpResponse::for_request(HttpStatus::UpgradeRequired, request)
+            .header(HttpHeader::Custom("Upgrade".to_string(), "websocket".to_string()))
+            .header(HttpHeader::Custom("Sec-WebSocket-Version".to_string(), WEBSOCKET_VERSION.to_string()))
+            .text("This resource is only available over WebSocket")
+            .write_to(stream)?;
+        return Ok(None);
+    }
+    if request.version() != HttpVersion::Http11 {
+        return Err(ServerError::BadRequest("WebSocket handshakes need HTTP/1.1".to_string()));
+    }
+    let key = request.get_header("Sec-WebSocket-Key").unwrap_or_default();
+    if base64::decode(key.trim()).is_none_or(|nonce| nonce.len() != 16) {
+        return Err(ServerError::BadRequest("Invalid Sec-WebSocket-Key".to_string()));
+    }
+
+    let mut response = HttpResponse::for_request(HttpStatus::SwitchingProtocols, request)
+        .header(HttpHeader::Custom("Upgrade".to_string(), "websocket".to_string()))
+        .header(HttpHeader::Custom("Sec-WebSocket-Accept".to_string(), accept_key(&key)));
+    // replaces the `Connection: close` a client expecting to reuse the connection would get
+    response.headers_mut().set(HttpHeader::Custom("Connection".to_string(), "Upgrade".to_string()));
+    response.write_to(stream)?;
+    // the connection now stays open for as long as either side wants it,
+    // waiting on the next message is no longer a slow request
+    stream.get_ref().set_read_timeout(None)?;
+    Ok(Some(WebSocket::new(stream)))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Opcode {
+    Continuation,
+    Text,
+    Binary,
+    Close,
+    Ping,
+    Pong,
+}
+
+impl Opcode {
+    pub fn from_u8(opcode: u8) -> Option<Self> {
+        match opcode {
+            0x0 => Some(Opcode::Continuation),
+            0x1 => Some(Opcode::Text),
+            0x2 => Some(Opcode::Binary),
+            0x8 => Some(Opcode::Close),
+            0x9 => Some(Opcode::Ping),
+            0xA => Some(Opcode::Pong),
+            _ => None,
+        }
+    }
+
+    pub fn as_u8(&self) -> u8 {
+        match self {
+            Opcode::Continuation => 0x0,
+            Opcode::Text => 0x1,
+            Opcode::Binary => 0x2,
+            Opcode::Close => 0x8,
+            Opcode::Ping => 0x9,
+            Opcode::Pong => 0xA,
+        }
+    }
+
+    /// Close, ping and pong, which can arrive between the fragments of a message
+    pub fn is_control(&self) -> bool {
+        self.as_u8() & 0x8 != 0
+    }
+}
+
+/// Status codes sent in close frames
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CloseCode {
+    Normal,
+    GoingAway,
+    ProtocolError,
+    UnsupportedData,
+    /// Never sent, stands for a close frame that had no code
+    NoStatus,
+    /// Never sent, stands for a connection lost without a close frame
+    Abnormal,
+    InvalidPayload,
+    PolicyViolation,
+    MessageTooBig,
+    MandatoryExtension,
+    InternalError,
+    /// Codes for libraries and applications, 3000 to 4999
+    Other(u16),
+}
+
+impl CloseCode {
+    /// The code a close frame carries, None for reserved or unassigned ones a peer must not send
+    pub fn from_u16(code: u16) -> Option<Self> {
+        match code {
+            1000 => Some(CloseCode::Normal),
+            1001 => Some(CloseCode::GoingAway),
+            1002 => Some(CloseCode::ProtocolError),
+            1003 => Some(CloseCode::UnsupportedData),
+            1007 => Some(CloseCode::InvalidPayload),
+            1008 => Some(CloseCode::PolicyViolation),
+            1009 => Some(CloseCode::MessageTooBig),
+            1010 => Some(CloseCode::MandatoryExtension),
+            1011 => Some(CloseCode::InternalError),
+            3000..=4999 => Some(CloseCode::Other(code)),
+            _ => None,
+        }
+    }
+
+    pub fn code(&self) -> u16 {
+        match self {
+            CloseCode::Normal => 1000,
+            CloseCode::GoingAway => 1001,
+            CloseCode::ProtocolError => 1002,
+            CloseCode::UnsupportedData => 1003,
+            CloseCode::NoStatus => 1005,
+            CloseCode::Abnormal => 1006,
+            CloseCode::InvalidPayload => 1007,
+            CloseCode::PolicyViolation => 1008,
+            CloseCode::MessageTooBig => 1009,
+            CloseCode::MandatoryExtension => 1010,
+            CloseCode::InternalError => 1011,
+            CloseCode::Other(code) => *code,
+        }
+    }
+}
+
+/// Why a WebSocket connection failed
+#[derive(Debug)]
+pub enum WebSocketError {
+    Io(io::Error),
+    /// The peer broke the protocol, the connection is closed with 1002
+    Protocol(&'static str),
+    /// A text message that isn't UTF-8, closed with 1007
+    InvalidUtf8,
+    /// A message over the size limit, closed with 1009
+    MessageTooBig,
+    /// Sending or receiving after the close handshake
+    Closed,
+}
+
+impl WebSocketError {
+    /// The code to close the connection with, None if it can't be closed cleanly
+    pub fn close_code(&self) -> Option<CloseCode> {
+        match self {
+            WebSocketError::Protocol(_) => Some(CloseCode::ProtocolError),
+            WebSocketError::InvalidUtf8 => Some(CloseCode::InvalidPayload),
+            WebSocketError::MessageTooBig => Some(CloseCode::MessageTooBig),
+            WebSocketError::Io(_) | WebSocketError::Closed => None,
+        }
+    }
+}
+
+impl fmt::Display for WebSocketError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            WebSocketError::Io(e) => write!(f, "{}", e),
+            WebSocketError::Protocol(reason) => write!(f, "WebSocket protocol error: {}", reason),
+            WebSocketError::InvalidUtf8 => write!(f, "WebSocket text message is not valid UTF-8"),
+            WebSocketError::MessageTooBig => write!(f, "WebSocket message too big"),
+            WebSocketError::Closed => write!(f, "WebSocket connection already closed"),
+        }
+    }
+}
+
+impl std::error::Error for WebSocketError {}
+
+impl From<io::Error> for WebSocketError {
+    fn from(e: io::Error) -> Self {
+        WebSocketError::Io(e)
+    }
+}
+
+impl From<WebSocketError> for ServerError {
+    fn from(e: WebSocketError) -> Self {
+        match e {
+            WebSocketError::Io(e) => ServerError::Io(e),
+            e => ServerError::BadRequest(e.to_string()),
+        }
+    }
+}
+
+/// A single frame on the wire
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    /// Whether this is the last frame of its message
+    pub fin: bool,
+    pub opcode: Opcode,
+    /// Clients mask every frame they send, servers never do
+    pub mask: Option<[u8; 4]>,
+    /// The payload, unmasked
+    pub payload: Vec<u8>,
+}
+
+impl Frame {
+    /// An unmasked frame that completes its message
+    pub fn new(opcode: Opcode, payload: Vec<u8>) -> Self {
+        Frame { fin: true, opcode, mask: None, payload }
+    }
+
+    /// Marks whether more fragments of the message follow
+    pub fn with_fin(mut self, fin: bool) -> Self {
+        self.fin = fin;
+        self
+    }
+
+    pub fn with_mask(mut self, mask: [u8; 4]) -> Self {
+        self.mask = Some(mask);
+        self
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.payload.len() + 14);
+        bytes.push(if self.fin { 0x80 } else { 0 } | self.opcode.as_u8());
+        let mask_bit = if self.mask.is_some() { 0x80 } else { 0 };
+        // the length takes 7 bits, or 7 bits flagging a 16 or 64 bit length after them
+        match self.payload.len() {
+            length @ 0..=125 => bytes.push(mask_bit | length as u8),
+            length @ 126..=0xffff => {
+                bytes.push(mask_bit | 126);
+                bytes.extend_from_slice(&(length as u16).to_be_bytes());
+            }
+            length => {
+                bytes.push(mask_bit | 127);
+                bytes.extend_from_slice(&(length as u64).to_be_bytes());
+            }
+        }
+        match self.mask {
+            Some(mask) => {
+                bytes.extend_from_slice(&mask);
+                bytes.extend(self.payload.iter().enumerate().map(|(i, byte)| byte ^ mask[i % 4]));
+            }
+            None => bytes.extend_from_slice(&self.payload),
+        }
+        bytes
+    }
+
+    /// Reads the next frame, unmasking its payload, payloads longer than `max_payload` are refused
+    /// before they are read
+    pub fn read_from<R: Read>(reader: &mut R, max_payload: usize) -> Result<Frame, WebSocketError> {
+        let mut head = [0u8; 2];
+        reader.read_exact(&mut head)?;
+        // no extensions are negotiated, so the reserved bits must be clear
+        if head[0] & 0x70 != 0 {
+            return Err(WebSocketError::Protocol("reserved bits set"));
+        }
+        let fin = head[0] & 0x80 != 0;
+        let opcode = Opcode::from_u8(head[0] & 0x0f).ok_or(WebSocketError::Protocol("unknown opcode"))?;
+        let masked = head[1] & 0x80 != 0;
+        let length = match head[1] & 0x7f {
+            126 => {
+                let mut length = [0u8; 2];
+                reader.read_exact(&mut length)?;
+                u16::from_be_bytes(length) as u64
+            }
+            127 => {
+                let mut length = [0u8; 8];
+                reader.read_exact(&mut length)?;
+                let length = u64::from_be_bytes(length);
+                if length >> 63 != 0 {
+                    return Err(WebSocketError::Protocol("payload length has its top bit set"));
+                }
+                length
+            }
+            length => length as u64,
+        };
+        if opcode.is_control() && (!fin || length > MAX_CONTROL_PAYLOAD as u64) {
+            return Err(WebSocketError::Protocol("control frames can't be fragmented or longer than 125 bytes"));
+        }
+        if length > max_payload as u64 {
+            return Err(WebSocketError::MessageTooBig);
+        }
+        let mask = if masked {
+            let mut mask = [0u8; 4];
+            reader.read_exact(&mut mask)?;
+            Some(mask)
+        } else {
+            None
+        };
+        let mut payload = vec![0u8; length as usize];
+        reader.read_exact(&mut payload)?;
+        if let Some(mask) = mask {
+            payload.iter_mut().enumerate().for_each(|(i, byte)| *byte ^= mask[i % 4]);
+        }
+        Ok(Frame { fin, opcode, mask, payload })
+    }
+}
+
+/// A complete message, fragments already joined
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Message {
+    Text(String),
+    Binary(Vec<u8>),
+    /// Already answered with a pong by the time it is received
+    Ping(Vec<u8>),
+    Pong(Vec<u8>),
+    /// The peer closed the connection, the close has already been answered
+    Close(Option<(CloseCode, String)>),
+}
+
+/// The server's end of a WebSocket connection, after the handshake
+///
+/// `recv` joins fragmented messages, answers pings and echoes the close handshake,
+/// a protocol violation by the client closes the connection with the matching code
+pub struct WebSocket<S: Read + Write> {
+    stream: S,
+    max_message_size: usize,
+    /// The start of a fragmented message, kept while control frames arrive between its fragments
+    partial: Option<(Opcode, Vec<u8>)>,
+    close_sent: bool,
+    close_received: bool,
+}
+
+impl<S: Read + Write> WebSocket<S> {
+    /// Wraps a stream the handshake has been completed on
+    pub fn new(stream: S) -> Self {
+        WebSocket { stream, max_message_size: DEFAULT_MAX_MESSAGE_SIZE, partial: None, close_sent: false, close_received: false }
+    }
+
+    pub fn with_max_message_size(mut self, max_message_size: usize) -> Self {
+        self.max_message_size = max_message_size;
+        self
+    }
+
+    /// Whether the close handshake has started from either side
+    pub fn is_closed(&self) -> bool {
+        self.close_sent || self.close_received
+    }
+
+    fn write_frame(&mut self, frame: Frame) -> Result<(), WebSocketError> {
+        if self.close_sent {
+            return Err(WebSocketError::Closed);
+        }
+        self.close_sent = frame.opcode == Opcode::Close;
+        self.stream.write_all(&frame.to_bytes())?;
+        self.stream.flush()?;
+        Ok(())
+    }
+
+    pub fn send_text(&mut self, text: &str) -> Result<(), WebSocketError> {
+        self.write_frame(Frame::new(Opcode::Text, text.as_bytes().to_vec()))
+    }
+
+    pub fn send_binary(&mut self, data: &[u8]) -> Result<(), WebSocketError> {
+        self.write_frame(Frame::new(Opcode::Binary, data.to_vec()))
+    }
+
+    /// Sends a message split into frames of at most `fragment_size` bytes, text is only split
+    /// on the wire so fragments may end partway through a character
+    pub fn send_fragmented(&mut self, message: &Message, fragment_size: usize) -> Result<(), WebSocketError> {
+        let (opcode, payload) = match message {
+            Message::Text(text) => (Opcode::Text, text.as_bytes()),
+            Message::Binary(data) => (Opcode::Binary, data.as_slice()),
+            _ => return Err(WebSocketError::Protocol("only text and binary messages can be fragmented")),
+        };
+        let fragments: Vec<&[u8]> = payload.chunks(fragment_size.max(1)).collect();
+        if fragments.is_empty() {
+            return self.write_frame(Frame::new(opcode, Vec::new()));
+        }
+        let last = fragments.len() - 1;
+        for (i, fragment) in fragments.into_iter().enumerate() {
+            let opcode = if i == 0 { opcode } else { Opcode::Continuation };
+            self.write_frame(Frame::new(opcode, fragment.to_vec()).with_fin(i == last))?;
+        }
+        Ok(())
+    }
+
+    pub fn ping(&mut self, payload: &[u8]) -> Result<(), WebSocketError> {
+        if payload.len() > MAX_CONTROL_PAYLOAD {
+            return Err(WebSocketError::Protocol("ping payloads are at most 125 bytes"));
+        }
+        self.write_frame(Frame::new(Opcode::Ping, payload.to_vec()))
+    }
+
+    /// Starts the close handshake, the reason is cut short to fit in a control frame
+    pub fn close(&mut self, code: CloseCode, reason: &str) -> Result<(), WebSocketError> {
+        let mut payload = code.code().to_be_bytes().to_vec();
+        let mut end = reason.len().min(MAX_CONTROL_PAYLOAD - 2);
+        while !reason.is_char_boundary(end) {
+            end -= 1;
+        }
+        payload.extend_from_slice(&reason.as_bytes()[..end]);
+        self.write_frame(Frame::new(Opcode::Close, payload))
+    }
+
+    /// Sends a close frame unless one has been sent already, for a connection its handler has
+    /// finished with without closing
+    pub fn close_if_open(&mut self, code: CloseCode) -> Result<(), WebSocketError> {
+        if self.close_sent {
+            return Ok(());
+        }
+        self.close(code, "")
+    }
+
+    /// Waits for the next message, once the client has closed the connection there are no more
+    pub fn recv(&mut self) -> Result<Message, WebSocketError> {
+        if self.close_received {
+            return Err(WebSocketError::Closed);
+        }
+        match self.read_message() {
+            Err(e) => {
+                // best effort, the client may well be gone already
+                if let Some(code) = e.close_code() && !self.close_sent {
+                    let _ = self.close(code, &e.to_string());
+                }
+                Err(e)
+            }
+            message => message,
+        }
+    }
+
+    fn read_message(&mut self) -> Result<Message, WebSocketError> {
+        loop {
+            let frame = Frame::read_from(&mut self.stream, self.max_message_size)?;
+            if frame.mask.is_none() {
+                return Err(WebSocketError::Protocol("client frames must be masked"));
+            }
+            match frame.opcode {
+                Opcode::Ping => {
+                    if !self.close_sent {
+                        self.write_frame(Frame::new(Opcode::Pong, frame.payload.clone()))?;
+                    }
+                    return Ok(Message::Ping(frame.payload));
+                }
+                Opcode::Pong => return Ok(Message::Pong(frame.payload)),
+                Opcode::Close => return self.receive_close(&frame.payload),
+                Opcode::Text | Opcode::Binary if self.partial.is_none() => self.partial = Some((frame.opcode, frame.payload)),
+                Opcode::Continuation => match &mut self.partial {
+                    Some((_, payload)) => {
+                        if payload.len() + frame.payload.len() > self.max_message_size {
+                            return Err(WebSocketError::MessageTooBig);
+                        }
+                        payload.extend_from_slice(&frame.payload);
+                    }
+                    None => return Err(WebSocketError::Protocol("continuation without a message to continue")),
+                },
+                Opcode::Text | Opcode::Binary => return Err(WebSocketError::Protocol("new message before the last one finished")),
+            }
+            if frame.fin {
+                return match self.partial.take() {
+                    Some((Opcode::Text, payload)) => String::from_utf8(payload)
+                        .map(Message::Text)
+                        .map_err(|_| WebSocketError::InvalidUtf8),
+                    Some((_, payload)) => Ok(Message::Binary(payload)),
+                    None => unreachable!("a data frame always starts or continues a message"),
+                };
+            }
+        }
+    }
+
+    /// Answers a close frame with one of our own, unless we started the close
+    fn receive_close(&mut self, payload: &[u8]) -> Result<Message, WebSocketError> {
+        let close = match payload {
+            [] => None,
+            [_] => return Err(WebSocketError::Protocol("close frame with a one byte payload")),
+            [high, low, reason @ ..] => {
+                let code = CloseCode::from_u16(u16::from_be_bytes([*high, *low]))
+                    .ok_or(WebSocketError::Protocol("invalid close code"))?;
+                let reason = std::str::from_utf8(reason).map_err(|_| WebSocketError::InvalidUtf8)?;
+                Some((code, reason.to_string()))
+            }
+        };
+        self.close_received = true;
+        if !self.close_sent {
+            let code = close.as_ref().map_or(CloseCode::Normal, |(code, _)| *code);
+            self.close(code, "")?;
+        }
+        Ok(Message::Close(close))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    const MASK: [u8; 4] = [0x37, 0xfa, 0x21, 0x3d];
+
+    /// A connection with the client's frames queued up to be read, and what the server writes kept
+    struct Connection {
+        input: Cursor<Vec<u8>>,
+        output: Vec<u8>,
+    }
+
+    impl Read for Connection {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.input.read(buf)
+        }
+    }
+
+    impl Write for Connection {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.output.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn websocket(client_frames: &[Frame]) -> WebSocket<Connection> {
+        let input = client_frames.iter().flat_map(|frame| frame.clone().with_mask(MASK).to_bytes()).collect();
+        WebSocket::new(Connection { input: Cursor::new(input), output: Vec::new() })
+    }
+
+    fn sent_frames(websocket: WebSocket<Connection>) -> Vec<Frame> {
+        let mut output = Cursor::new(websocket.stream.output);
+        let mut frames = Vec::new();
+        while (output.position() as usize) < output.get_ref().len() {
+            frames.push(Frame::read_from(&mut output, usize::MAX).unwrap());
+        }
+        frames
+    }
+
+    #[test]
+    fn test_accept_key() {
+        // the example from RFC 6455
+        assert_eq!(accept_key("dGhlIHNhbXBsZSBub25jZQ=="), "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+    }
+
+    #[test]
+    fn test_frame_serialization() {
+        // examples from RFC 6455 section 5.7
+        assert_eq!(Frame::new(Opcode::Text, b"Hello".to_vec()).to_bytes(), b"\x81\x05Hello");
+        let masked = Frame::new(Opcode::Text, b"Hello".to_vec()).with_mask(MASK).to_bytes();
+        assert_eq!(masked, b"\x81\x85\x37\xfa\x21\x3d\x7f\x9f\x4d\x51\x58");
+        assert_eq!(Frame::read_from(&mut masked.as_slice(), 100).unwrap().payload, b"Hello");
+        assert_eq!(Frame::new(Opcode::Text, b"Hel".to_vec()).with_fin(false).to_bytes(), b"\x01\x03Hel");
+
+        for length in [125, 126, 0xffff, 0x10000] {
+            let frame = Frame::new(Opcode::Binary, vec![7; length]);
+            let bytes = frame.to_bytes();
+            assert_eq!(bytes.len() - length, match length { 0..=125 => 2, 126..=0xffff => 4, _ => 10 });
+            assert_eq!(Frame::read_from(&mut bytes.as_slice(), length).unwrap(), frame);
+        }
+        assert!(matches!(Frame::read_from(&mut &b"\x82\x7e\x01\x00"[..], 255), Err(WebSocketError::MessageTooBig)));
+        assert!(matches!(Frame::read_from(&mut &b"\xc1\x00"[..], 255), Err(WebSocketError::Protocol(_))));
+        assert!(matches!(Frame::read_from(&mut &b"\x09\x00"[..], 255), Err(WebSocketError::Protocol(_))));
+    }
+
+    #[test]
+    fn test_fragments_pings_and_close() {
+        let mut websocket = websocket(&[
+            Frame::new(Opcode::Text, b"Hel".to_vec()).with_fin(false),
+            Frame::new(Opcode::Ping, b"are you there".to_vec()),
+            Frame::new(Opcode::Continuation, b"lo".to_vec()),
+            Frame::new(Opcode::Binary, vec![1, 2, 3]),
+            Frame::new(Opcode::Close, b"\x03\xe8bye".to_vec()),
+        ]);
+        assert_eq!(websocket.recv().unwrap(), Message::Ping(b"are you there".to_vec()));
+        assert_eq!(websocket.recv().unwrap(), Message::Text("Hello".to_string()));
+        websocket.send_fragmented(&Message::Text("Hello".to_string()), 2).unwrap();
+        assert_eq!(websocket.recv().unwrap(), Message::Binary(vec![1, 2, 3]));
+        assert_eq!(websocket.recv().unwrap(), Message::Close(Some((CloseCode::Normal, "bye".to_string()))));
+        assert!(websocket.is_closed());
+        assert!(matches!(websocket.send_text("too late"), Err(WebSocketError::Closed)));
+
+        let sent = sent_frames(websocket);
+        assert_eq!(sent, vec![
+            Frame::new(Opcode::Pong, b"are you there".to_vec()),
+            Frame::new(Opcode::Text, b"He".to_vec()).with_fin(false),
+            Frame::new(Opcode::Continuation, b"ll".to_vec()).with_fin(false),
+            Frame::new(Opcode::Continuation, b"o".to_vec()),
+            Frame::new(Opcode::Close, b"\x03\xe8".to_vec()),
+        ]);
+    }
+
+    #[test]
+    fn test_protocol_errors_close_the_connection() {
+        let close_code = |client_frames: &[Frame], max_message_size: usize| {
+            let mut websocket = websocket(client_frames).with_max_message_size(max_message_size);
+            assert!(websocket.recv().is_err());
+            let sent = sent_frames(websocket);
+            u16::from_be_bytes([sent[0].payload[0], sent[0].payload[1]])
+        };
+        assert_eq!(close_code(&[Frame::new(Opcode::Continuation, b"x".to_vec())], 100), 1002);
+        assert_eq!(close_code(&[Frame::new(Opcode::Text, vec![0xff, 0xfe])], 100), 1007);
+        assert_eq!(close_code(&[
+            Frame::new(Opcode::Binary, vec![0; 60]).with_fin(false),
+            Frame::new(Opcode::Continuation, vec![0; 60]),
+        ], 100), 1009);
+        assert_eq!(close_code(&[Frame::new(Opcode::Close, b"\x03\xed".to_vec())], 100), 1002);
+
+        // unmasked frames from a client are refused
+        let mut websocket = WebSocket::new(Connection {
+            input: Cursor::new(Frame::new(Opcode::Text, b"hi".to_vec()).to_bytes()),
+            output: Vec::new(),
+        });
+        assert!(matches!(websocket.recv(), Err(WebSocketError::Protocol(_))));
+    }
+}
@@ -19,4 +19,13 @@ function main() {
     // Clear the color buffer with specified clear color
     gl.clear(gl.COLOR_BUFFER_BIT);
 
+    // Live data channel, the server answers each frame request with the next clear color
+    const socket = new WebSocket(`ws://${window.location.host}/webgl_live`);
+    socket.onopen = () => socket.send("frame");
+    socket.onmessage = (event) => {
+        const [r, g, b] = event.data.split(" ").map(Number);
+        gl.clearColor(r, g, b, 1.0);
+        gl.clear(gl.COLOR_BUFFER_BIT);
+        window.requestAnimationFrame(() => socket.send("frame"));
+    };
 }
\ No newline at end of file